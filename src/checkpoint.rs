@@ -0,0 +1,177 @@
+use crate::events::{Event, EventRecord, EventSink};
+use crate::history::data_dir;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+/// The tasks that completed in the last run of a job.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub completed: HashSet<String>,
+}
+
+/// Stores the checkpoint of every job of a workflow file in the data directory.
+pub struct CheckpointStore {
+    dir: PathBuf,
+}
+
+impl CheckpointStore {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// Opens the checkpoints in the data directory.
+    pub fn open() -> Result<Self> {
+        Ok(Self::new(data_dir()?.join("checkpoints")))
+    }
+
+    /// Returns the path of the checkpoint of the job in the file.
+    fn path(&self, file: &Path, job: &str) -> PathBuf {
+        let name: String = format!("{}-{}", file.to_string_lossy(), job)
+            .chars()
+            .map(|c| if c.is_alphanumeric() { c } else { '_' })
+            .collect();
+        self.dir.join(name + ".json")
+    }
+
+    /// Returns the checkpoint of the last run of the job. Empty if there is none.
+    pub fn load(&self, file: &Path, job: &str) -> Result<Checkpoint> {
+        let path = self.path(file, job);
+        if !path.exists() {
+            return Ok(Checkpoint::default());
+        }
+        let text = std::fs::read_to_string(&path).context(format!("Could not read {:?}", path))?;
+        serde_json::from_str(&text).context(format!("Checkpoint {:?} is invalid", path))
+    }
+
+    pub fn save(&self, file: &Path, job: &str, checkpoint: &Checkpoint) -> Result<()> {
+        std::fs::create_dir_all(&self.dir).context(format!("Could not create {:?}", self.dir))?;
+        let path = self.path(file, job);
+        let text = serde_json::to_string(checkpoint).context("Could not serialize checkpoint")?;
+        std::fs::write(&path, text).context(format!("Could not write {:?}", path))
+    }
+
+    pub fn remove(&self, file: &Path, job: &str) -> Result<()> {
+        let path = self.path(file, job);
+        if path.exists() {
+            std::fs::remove_file(&path).context(format!("Could not remove {:?}", path))?;
+        }
+        Ok(())
+    }
+}
+
+/// Saves the completed tasks of every job after each task. The checkpoint of a job is removed
+/// when the job succeeds.
+pub struct CheckpointSink {
+    store: CheckpointStore,
+    file: PathBuf,
+    /// the checkpoints the jobs are resumed from, in the order they are run
+    resumed: Vec<Checkpoint>,
+    current: Option<(String, Checkpoint)>,
+    jobs: usize,
+}
+
+impl CheckpointSink {
+    pub fn new(store: CheckpointStore, file: PathBuf, resumed: Vec<Checkpoint>) -> Self {
+        Self {
+            store,
+            file,
+            resumed,
+            current: None,
+            jobs: 0,
+        }
+    }
+}
+
+impl EventSink for CheckpointSink {
+    fn handle(&mut self, record: &EventRecord) -> Result<()> {
+        match &record.event {
+            Event::JobStarted { job } => {
+                // tasks that are skipped because they completed before stay completed
+                let checkpoint = self.resumed.get(self.jobs).cloned().unwrap_or_default();
+                self.jobs += 1;
+                self.current = Some((job.clone(), checkpoint));
+            }
+            Event::TaskFinished {
+                path,
+                cleanup: false,
+                ..
+            } => {
+                if let Some((job, checkpoint)) = self.current.as_mut() {
+                    checkpoint.completed.insert(path.clone());
+                    self.store.save(&self.file, job, checkpoint)?;
+                }
+            }
+            Event::JobFinished { job, success, .. } => {
+                if let Some((_, checkpoint)) = self.current.take() {
+                    if *success {
+                        self.store.remove(&self.file, job)?;
+                    } else {
+                        self.store.save(&self.file, job, &checkpoint)?;
+                    }
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Checkpoint, CheckpointSink, CheckpointStore};
+    use crate::events::{Event, EventRecord, EventSink};
+    use std::path::Path;
+
+    #[test]
+    fn checkpoint_test() {
+        let dir = std::env::temp_dir().join(format!("workflowo-checkpoint-{}", std::process::id()));
+        let file = Path::new("/project/workflowo.yaml");
+        let mut resumed = Checkpoint::default();
+        resumed.completed.insert("0".to_string());
+        let mut sink = CheckpointSink::new(
+            CheckpointStore::new(dir.clone()),
+            file.to_path_buf(),
+            vec![resumed],
+        );
+
+        let mut handle = |event| {
+            sink.handle(&EventRecord {
+                timestamp_ms: 0,
+                event,
+            })
+            .unwrap()
+        };
+        handle(Event::JobStarted {
+            job: "deploy".to_string(),
+        });
+        handle(Event::TaskFinished {
+            path: "1".to_string(),
+            task: "bash: make".to_string(),
+            duration_ms: 0,
+            output: Default::default(),
+            cleanup: false,
+        });
+        handle(Event::TaskFinished {
+            path: "2".to_string(),
+            task: "bash: make clean".to_string(),
+            duration_ms: 0,
+            output: Default::default(),
+            cleanup: true,
+        });
+        handle(Event::JobFinished {
+            job: "deploy".to_string(),
+            duration_ms: 0,
+            success: false,
+        });
+
+        let store = CheckpointStore::new(dir.clone());
+        let completed = store.load(file, "deploy").unwrap().completed;
+        assert!(completed.contains("0") && completed.contains("1"));
+        assert!(!completed.contains("2"));
+        assert!(store.load(file, "build").unwrap().completed.is_empty());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
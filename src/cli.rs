@@ -1,39 +1,434 @@
-use clap::{self, Parser};
-use std::path::PathBuf;
+use crate::history::Outcome;
+use crate::report::ReportFormat;
+use clap::{self, CommandFactory, Parser, Subcommand};
+use serde_yaml::Value;
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
 use std::process;
 
 #[derive(Parser, Debug)]
 #[command(version)]
 pub struct Args {
-    /// the configuration file
-    pub file: PathBuf,
+    /// the configuration file, if not given it is searched for in the current directory and its parents
+    pub file: Option<PathBuf>,
 
-    /// the job that should be executed
-    pub job: String,
+    /// the configuration file, the positional file takes precedence
+    #[arg(short = 'f', long = "file", env = "WORKFLOWO_FILE", global = true)]
+    pub file_option: Option<PathBuf>,
 
-    #[arg(short, long)]
+    #[arg(short, long, global = true)]
     pub verbose: bool,
+
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// List all jobs with their descriptions
+    List,
+
+    /// Print the task tree of a job
+    Describe {
+        /// the job followed by arguments for its params in the form `name=value`
+        #[arg(required = true)]
+        job: Vec<String>,
+    },
+
+    /// Parse all jobs and report every error
+    Validate,
+
+    /// Execute one or more jobs in sequence
+    Run {
+        /// the jobs, each followed by arguments for its params in the form `name=value`
+        #[arg(required = true)]
+        jobs: Vec<String>,
+
+        /// print the tasks that would be executed without executing them
+        #[arg(long)]
+        dry_run: bool,
+
+        /// `json` writes the events of the run as json lines to stdout, `json=<path>` to a file
+        #[arg(long, value_name = "FORMAT", value_parser = parse_output, default_value = "text")]
+        output: Output,
+
+        /// write a report of the run, `junit=<path>` or `markdown=<path>`, can be given multiple times
+        #[arg(long, value_name = "FORMAT=PATH", value_parser = parse_report)]
+        report: Vec<(ReportFormat, PathBuf)>,
+
+        /// write a log file of the run into the directory, overrides the dir of the LOG section
+        #[arg(long, value_name = "DIR")]
+        log_dir: Option<PathBuf>,
+
+        /// do not record the run in the history
+        #[arg(long)]
+        no_history: bool,
+
+        /// skip the tasks that completed in the last run of the job
+        #[arg(long, conflicts_with_all = ["from", "only"])]
+        resume: bool,
+
+        /// execute the task with the path (e.g. `2.0.1`) and all tasks after it
+        #[arg(long, value_name = "TASK_PATH", conflicts_with = "only")]
+        from: Option<String>,
+
+        /// only execute the task with the path (e.g. `2.0.1`)
+        #[arg(long, value_name = "TASK_PATH")]
+        only: Option<String>,
+
+        /// execute tasks with sources even if the sources did not change
+        #[arg(long)]
+        force: bool,
+
+        /// approve all `approve` tasks without asking
+        #[arg(
+            short,
+            long,
+            env = "WORKFLOWO_YES",
+            value_parser = clap::builder::FalseyValueParser::new()
+        )]
+        yes: bool,
+    },
+
+    /// Execute jobs again whenever watched files or the configuration file change
+    Watch {
+        /// the jobs, each followed by arguments for its params in the form `name=value`
+        #[arg(required = true)]
+        jobs: Vec<String>,
+
+        /// glob patterns of the watched files, e.g. `src/**`
+        #[arg(long, required = true, num_args = 1..)]
+        paths: Vec<String>,
+
+        /// milliseconds without changes before the jobs are executed
+        #[arg(long, value_name = "MS", default_value_t = 300)]
+        debounce: u64,
+    },
+
+    /// List the recorded runs, the newest last
+    History {
+        #[command(subcommand)]
+        command: Option<HistoryCommand>,
+
+        /// only runs of this job
+        #[arg(long)]
+        job: Option<String>,
+
+        /// only runs with this outcome
+        #[arg(long)]
+        outcome: Option<Outcome>,
+
+        /// the maximum number of runs
+        #[arg(short = 'n', long, default_value_t = 20)]
+        limit: usize,
+    },
+
+    /// Manage the encrypted secrets that are used with `!Secret`
+    Secrets {
+        #[command(subcommand)]
+        command: SecretsCommand,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum HistoryCommand {
+    /// Print the details of a run
+    Show { id: u64 },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum SecretsCommand {
+    /// Add or change a secret, the value is prompted for if not given
+    Set { name: String, value: Option<String> },
+    /// Print the value of a secret
+    Get { name: String },
+    /// List the names of all secrets
+    List,
+    /// Edit all secrets as yaml in `$EDITOR`
+    Edit,
+}
+
+/// The format of the output of a run.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Output {
+    /// messages for humans
+    Text,
+    /// json lines with the events to stdout or a file
+    Json(Option<PathBuf>),
+}
+
+fn parse_output(value: &str) -> Result<Output, String> {
+    match value.split_once('=') {
+        None if value == "text" => Ok(Output::Text),
+        None if value == "json" => Ok(Output::Json(None)),
+        Some(("json", path)) => Ok(Output::Json(Some(PathBuf::from(path)))),
+        _ => Err(format!(
+            "{} is not a valid output, expected text, json or json=<path>",
+            value
+        )),
+    }
+}
+
+fn parse_report(value: &str) -> Result<(ReportFormat, PathBuf), String> {
+    match value.split_once('=') {
+        Some(("junit", path)) if !path.is_empty() => Ok((ReportFormat::Junit, PathBuf::from(path))),
+        Some(("markdown", path)) if !path.is_empty() => {
+            Ok((ReportFormat::Markdown, PathBuf::from(path)))
+        }
+        _ => Err(format!(
+            "{} is not a valid report, expected junit=<path> or markdown=<path>",
+            value
+        )),
+    }
+}
+
+/// Parses the cli arguments. `run` is the default command, so `workflowo <file> <job>` still
+/// works.
+pub fn parse_args() -> Args {
+    Args::parse_from(with_default_command(std::env::args_os().collect()))
+}
+
+/// Inserts `run` after the positional file if no command follows it.
+fn with_default_command(mut args: Vec<OsString>) -> Vec<OsString> {
+    let command = Args::command();
+    let is_command = |word: &str| {
+        word == "help"
+            || command
+                .get_subcommands()
+                .any(|subcommand| subcommand.get_name() == word)
+    };
+
+    let mut positional = Vec::new();
+    let mut index = 1;
+    while index < args.len() && positional.len() < 2 {
+        match args[index].to_str() {
+            Some("--") => break,
+            // the value of the option is not positional
+            Some("-f" | "--file") => index += 1,
+            Some(word) if word.starts_with('-') => {}
+            Some(word) if positional.is_empty() && is_command(word) => return args,
+            _ => positional.push(index),
+        }
+        index += 1;
+    }
+    if let [_, job] = positional[..] {
+        if !args[job].to_str().is_some_and(is_command) {
+            args.insert(job, "run".into());
+        }
+    }
+    args
 }
 
-/// Parses the cli arguments given to the program and validates them.
+/// The exit codes for the different categories of failures.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExitCode {
+    /// a task failed while executing
+    TaskFailed = 1,
+    /// the cli arguments are invalid (also used by clap)
+    InvalidArguments = 2,
+    /// the configuration file does not exist or is not a yaml file
+    InvalidFile = 3,
+    /// the configuration file could not be read, rendered or parsed
+    InvalidWorkflow = 4,
+    /// a job was not found in the configuration file
+    JobNotFound = 5,
+    /// the run was cancelled by SIGINT or SIGTERM
+    Cancelled = 130,
+}
+
+impl ExitCode {
+    /// Exits the process with this exit code.
+    pub fn exit(self) -> ! {
+        process::exit(self as i32)
+    }
+}
+
+/// The names of the configuration files that are searched for.
+const WORKFLOW_FILE_NAMES: [&str; 4] = [
+    "workflowo.yaml",
+    "workflowo.yml",
+    ".workflowo.yaml",
+    ".workflowo.yml",
+];
+
+/// Searches the directory and its parents for a configuration file.
+pub fn find_workflow_file(start: &Path) -> Option<PathBuf> {
+    for dir in start.ancestors() {
+        for name in WORKFLOW_FILE_NAMES {
+            let path = dir.join(name);
+            if path.is_file() {
+                return Some(path);
+            }
+        }
+    }
+    None
+}
+
+/// Returns the path of the configuration file and validates it. Exits if it is not valid.
+///
+/// The file is the positional file, the `-f` option (or `WORKFLOWO_FILE`) or the first
+/// configuration file found in the current directory and its parents.
 ///
 /// Validates:
 /// - the file exists
 /// - the file is a file
 /// - the file has the extension yml or yaml
-pub fn parse_and_validate_args() -> Args {
-    let args = Args::parse();
+pub fn workflow_file(args: &Args) -> PathBuf {
+    let file = match args.file.clone().or(args.file_option.clone()) {
+        Some(file) => file,
+        None => {
+            let found = std::env::current_dir()
+                .ok()
+                .and_then(|current_dir| find_workflow_file(&current_dir));
+            match found {
+                Some(file) => file,
+                None => {
+                    eprintln!(
+                        "Error: no file given and none of {} found in the current directory or its parents!",
+                        WORKFLOW_FILE_NAMES.join(", ")
+                    );
+                    ExitCode::InvalidFile.exit();
+                }
+            }
+        }
+    };
 
-    if !args.file.exists() {
-        println!("Error: {} does not exist!", args.file.to_str().unwrap());
-        process::exit(-1);
-    } else if !args.file.is_file() {
-        println!("Error: {} is not a file!", args.file.to_str().unwrap());
-        process::exit(-1);
-    } else if args.file.extension().unwrap() != "yml" && args.file.extension().unwrap() != "yaml" {
-        println!("Error: {} is not a yaml file!", args.file.to_str().unwrap());
-        process::exit(-1);
+    if !file.exists() {
+        eprintln!("Error: {} does not exist!", file.to_str().unwrap());
+        ExitCode::InvalidFile.exit();
+    } else if !file.is_file() {
+        eprintln!("Error: {} is not a file!", file.to_str().unwrap());
+        ExitCode::InvalidFile.exit();
+    } else if file
+        .extension()
+        .is_none_or(|extension| extension != "yml" && extension != "yaml")
+    {
+        eprintln!("Error: {} is not a yaml file!", file.to_str().unwrap());
+        ExitCode::InvalidFile.exit();
     }
 
-    args
+    file
+}
+
+/// A job given on the cli with the arguments for its params.
+#[derive(Debug, PartialEq)]
+pub struct JobCall {
+    pub name: String,
+    pub args: HashMap<String, Value>,
+}
+
+/// Splits the values into jobs and the `name=value` arguments that follow each job.
+pub fn parse_job_calls(values: &[String]) -> anyhow::Result<Vec<JobCall>> {
+    let mut calls: Vec<JobCall> = Vec::new();
+    for value in values {
+        match value.split_once('=') {
+            Some((name, arg)) => match calls.last_mut() {
+                Some(call) => {
+                    call.args
+                        .insert(name.to_string(), Value::String(arg.to_string()));
+                }
+                None => anyhow::bail!(format!("argument {} is not preceded by a job", value)),
+            },
+            None => calls.push(JobCall {
+                name: value.to_owned(),
+                args: HashMap::new(),
+            }),
+        }
+    }
+    Ok(calls)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        find_workflow_file, parse_job_calls, with_default_command, Args, Command, JobCall,
+    };
+    use clap::Parser;
+    use serde_yaml::Value;
+    use std::collections::HashMap;
+
+    #[test]
+    fn find_workflow_file_test() {
+        let root = std::env::temp_dir().join(format!("workflowo-find-{}", std::process::id()));
+        let nested = root.join("a").join("b");
+        std::fs::create_dir_all(&nested).unwrap();
+        std::fs::write(root.join(".workflowo.yml"), "").unwrap();
+
+        assert_eq!(
+            find_workflow_file(&nested),
+            Some(root.join(".workflowo.yml"))
+        );
+
+        std::fs::write(root.join("a").join("workflowo.yaml"), "").unwrap();
+        assert_eq!(
+            find_workflow_file(&nested),
+            Some(root.join("a").join("workflowo.yaml"))
+        );
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn parse_job_calls_test() {
+        let values: Vec<String> = ["build", "deploy", "host=a", "version=1.2"]
+            .iter()
+            .map(|value| value.to_string())
+            .collect();
+        let mut deploy_args = HashMap::new();
+        deploy_args.insert("host".to_string(), Value::String("a".to_string()));
+        deploy_args.insert("version".to_string(), Value::String("1.2".to_string()));
+        assert_eq!(
+            parse_job_calls(&values).unwrap(),
+            vec![
+                JobCall {
+                    name: "build".to_string(),
+                    args: HashMap::new()
+                },
+                JobCall {
+                    name: "deploy".to_string(),
+                    args: deploy_args
+                }
+            ]
+        );
+
+        assert!(parse_job_calls(&["host=a".to_string()]).is_err());
+    }
+
+    #[test]
+    fn default_command_test() {
+        let args = |line: &str| line.split(' ').map(Into::into).collect::<Vec<_>>();
+        assert_eq!(
+            with_default_command(args("workflowo file.yaml deploy host=a version=1.2")),
+            args("workflowo file.yaml run deploy host=a version=1.2")
+        );
+        assert_eq!(
+            with_default_command(args("workflowo -v file.yaml deploy --dry-run")),
+            args("workflowo -v file.yaml run deploy --dry-run")
+        );
+        for line in [
+            "workflowo file.yaml list",
+            "workflowo run deploy",
+            "workflowo -f file.yaml describe deploy",
+            "workflowo file.yaml",
+            "workflowo history show 12",
+        ] {
+            assert_eq!(with_default_command(args(line)), args(line));
+        }
+    }
+
+    #[test]
+    fn approve_args_test() {
+        let yes = |args: &[&str]| match Args::try_parse_from(args).unwrap().command {
+            Command::Run { yes, .. } => yes,
+            command => panic!("parsed {:?}", command),
+        };
+        assert!(!yes(&["workflowo", "run", "deploy"]));
+        assert!(yes(&["workflowo", "run", "deploy", "--yes"]));
+        std::env::set_var("WORKFLOWO_YES", "1");
+        assert!(yes(&["workflowo", "run", "deploy"]));
+        std::env::set_var("WORKFLOWO_YES", "no");
+        assert!(!yes(&["workflowo", "run", "deploy"]));
+        std::env::remove_var("WORKFLOWO_YES");
+    }
 }
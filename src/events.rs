@@ -0,0 +1,293 @@
+use crate::mask;
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::fmt;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Something that happened while running a job.
+///
+/// Tasks are identified by their path. The path is the index of the task in its parent, joined
+/// with the indices of its parents (e.g. `2.0.1`).
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    JobStarted {
+        job: String,
+    },
+    JobFinished {
+        job: String,
+        duration_ms: u64,
+        success: bool,
+    },
+    TaskStarted {
+        path: String,
+        task: String,
+    },
+    TaskFinished {
+        path: String,
+        task: String,
+        duration_ms: u64,
+        #[serde(flatten)]
+        output: TaskOutput,
+        /// set for the tasks of a `finally` block, they are executed again when a job is resumed
+        #[serde(skip_serializing_if = "std::ops::Not::not")]
+        cleanup: bool,
+    },
+    TaskSkipped {
+        path: String,
+        task: String,
+        reason: String,
+    },
+    TaskFailed {
+        path: String,
+        task: String,
+        duration_ms: u64,
+        #[serde(flatten)]
+        output: TaskOutput,
+        /// the error followed by its causes
+        error: Vec<String>,
+    },
+    /// An `approve` task was approved.
+    TaskApproved {
+        path: String,
+        task: String,
+        approved_by: String,
+    },
+}
+
+/// The exit code and captured output of a task. Only set by tasks that execute commands.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct TaskOutput {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stdout: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stderr: Option<String>,
+}
+
+impl Event {
+    /// Replaces the registered secrets in all texts of the event.
+    pub fn masked(self) -> Self {
+        match self {
+            Event::JobStarted { job } => Event::JobStarted {
+                job: mask::mask(&job),
+            },
+            Event::JobFinished {
+                job,
+                duration_ms,
+                success,
+            } => Event::JobFinished {
+                job: mask::mask(&job),
+                duration_ms,
+                success,
+            },
+            Event::TaskStarted { path, task } => Event::TaskStarted {
+                path,
+                task: mask::mask(&task),
+            },
+            Event::TaskFinished {
+                path,
+                task,
+                duration_ms,
+                output,
+                cleanup,
+            } => Event::TaskFinished {
+                path,
+                task: mask::mask(&task),
+                duration_ms,
+                output: output.masked(),
+                cleanup,
+            },
+            Event::TaskSkipped { path, task, reason } => Event::TaskSkipped {
+                path,
+                task: mask::mask(&task),
+                reason: mask::mask(&reason),
+            },
+            Event::TaskFailed {
+                path,
+                task,
+                duration_ms,
+                output,
+                error,
+            } => Event::TaskFailed {
+                path,
+                task: mask::mask(&task),
+                duration_ms,
+                output: output.masked(),
+                error: error.iter().map(|cause| mask::mask(cause)).collect(),
+            },
+            Event::TaskApproved {
+                path,
+                task,
+                approved_by,
+            } => Event::TaskApproved {
+                path,
+                task: mask::mask(&task),
+                approved_by,
+            },
+        }
+    }
+}
+
+impl TaskOutput {
+    /// Replaces the registered secrets in the captured output.
+    pub fn masked(self) -> Self {
+        Self {
+            exit_code: self.exit_code,
+            stdout: self.stdout.map(|stdout| mask::mask(&stdout)),
+            stderr: self.stderr.map(|stderr| mask::mask(&stderr)),
+        }
+    }
+}
+
+/// An [`Event`] with the time it was emitted at.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EventRecord {
+    /// milliseconds since the unix epoch
+    pub timestamp_ms: u64,
+    #[serde(flatten)]
+    pub event: Event,
+}
+
+/// Receives the events of a run.
+pub trait EventSink: Send {
+    fn handle(&mut self, record: &EventRecord) -> Result<()>;
+
+    /// Will be called once after the run.
+    fn finish(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Distributes events to all registered sinks. Clones share the sinks.
+#[derive(Clone, Default)]
+pub struct EventEmitter {
+    sinks: Arc<Mutex<Vec<Box<dyn EventSink>>>>,
+}
+
+impl EventEmitter {
+    pub fn add_sink(&self, sink: Box<dyn EventSink>) {
+        self.sinks.lock().unwrap().push(sink);
+    }
+
+    /// Sends the event to all sinks. A failing sink does not stop the run. Secrets are masked
+    /// before the sinks receive the event.
+    pub fn emit(&self, event: Event) {
+        let record = EventRecord {
+            timestamp_ms: now_ms(),
+            event: event.masked(),
+        };
+        for sink in self.sinks.lock().unwrap().iter_mut() {
+            if let Err(error) = sink.handle(&record) {
+                eprintln!("Warning: could not handle event: {:#}", error);
+            }
+        }
+    }
+
+    /// Finishes all sinks.
+    pub fn finish(&self) -> Result<()> {
+        for sink in self.sinks.lock().unwrap().iter_mut() {
+            sink.finish()?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for EventEmitter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "EventEmitter")
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+/// Writes every event as one line of json.
+pub struct JsonLinesSink {
+    writer: Box<dyn Write + Send>,
+}
+
+impl JsonLinesSink {
+    pub fn stdout() -> Self {
+        Self {
+            writer: Box::new(std::io::stdout()),
+        }
+    }
+
+    pub fn file(path: &PathBuf) -> Result<Self> {
+        let file = std::fs::File::create(path)
+            .context(format!("Could not create event file {:?}", path))?;
+        Ok(Self {
+            writer: Box::new(std::io::BufWriter::new(file)),
+        })
+    }
+}
+
+impl EventSink for JsonLinesSink {
+    fn handle(&mut self, record: &EventRecord) -> Result<()> {
+        serde_json::to_writer(&mut self.writer, record).context("Could not serialize event")?;
+        self.writer
+            .write_all(b"\n")
+            .context("Could not write event")?;
+        self.writer.flush().context("Could not write event")
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.writer.flush().context("Could not write events")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Event, EventRecord, TaskOutput};
+    use crate::mask;
+
+    #[test]
+    fn masked_event_test() {
+        mask::register("masked-event-test-secret");
+        let event = Event::TaskFailed {
+            path: "0".to_string(),
+            task: "bash: login masked-event-test-secret".to_string(),
+            duration_ms: 0,
+            output: TaskOutput {
+                exit_code: Some(1),
+                stdout: Some("masked-event-test-secret".to_string()),
+                stderr: None,
+            },
+            error: vec!["wrong password masked-event-test-secret".to_string()],
+        };
+        let json = serde_json::to_string(&event.masked()).unwrap();
+        assert!(!json.contains("masked-event-test-secret"));
+        assert!(json.contains(mask::MASK));
+    }
+
+    #[test]
+    fn serialize_event_test() {
+        let record = EventRecord {
+            timestamp_ms: 10,
+            event: Event::TaskFinished {
+                path: "0.1".to_string(),
+                task: "bash: ls".to_string(),
+                duration_ms: 5,
+                output: TaskOutput {
+                    exit_code: Some(0),
+                    stdout: Some("a.txt\n".to_string()),
+                    stderr: None,
+                },
+                cleanup: false,
+            },
+        };
+        assert_eq!(
+            serde_json::to_string(&record).unwrap(),
+            r#"{"timestamp_ms":10,"event":"task_finished","path":"0.1","task":"bash: ls","duration_ms":5,"exit_code":0,"stdout":"a.txt\n"}"#
+        );
+    }
+}
@@ -0,0 +1,273 @@
+use crate::events::{Event, EventRecord, EventSink};
+use crate::mask;
+use anyhow::{Context, Result};
+use chrono::{Local, TimeZone};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::OpenOptions;
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+
+/// Returns the directory where workflowo stores its data. `WORKFLOWO_DATA_DIR` overrides the data
+/// directory of the user.
+pub fn data_dir() -> Result<PathBuf> {
+    if let Some(dir) = std::env::var_os("WORKFLOWO_DATA_DIR") {
+        return Ok(PathBuf::from(dir));
+    }
+    Ok(dirs::data_dir()
+        .context("Could not find the data directory of the user")?
+        .join("workflowo"))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    Success,
+    Failed,
+}
+
+/// One job that was run.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RunRecord {
+    pub id: u64,
+    pub file: PathBuf,
+    pub job: String,
+    pub user: Option<String>,
+    /// the arguments for the params of the job
+    pub args: BTreeMap<String, String>,
+    /// the prompts and answers of the inputs that are not hidden
+    pub inputs: Vec<(String, String)>,
+    /// milliseconds since the unix epoch
+    pub started_ms: u64,
+    pub finished_ms: u64,
+    pub outcome: Outcome,
+    /// the path of the task that caused the failure
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub failed_task: Option<String>,
+    /// the error followed by its causes
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub error: Vec<String>,
+}
+
+/// The history of all runs, stored as json lines.
+pub struct History {
+    path: PathBuf,
+}
+
+impl History {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    /// Opens the history in the data directory.
+    pub fn open() -> Result<Self> {
+        Ok(Self::new(data_dir()?.join("history.jsonl")))
+    }
+
+    /// Returns all runs, the oldest first.
+    pub fn records(&self) -> Result<Vec<RunRecord>> {
+        if !self.path.exists() {
+            return Ok(Vec::new());
+        }
+        let file = std::fs::File::open(&self.path)
+            .context(format!("Could not open history {:?}", self.path))?;
+        let mut records = Vec::new();
+        for (index, line) in BufReader::new(file).lines().enumerate() {
+            let line = line.context(format!("Could not read history {:?}", self.path))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            records.push(serde_json::from_str(&line).context(format!(
+                "Line {} of history {:?} is invalid",
+                index + 1,
+                self.path
+            ))?);
+        }
+        Ok(records)
+    }
+
+    /// Appends the run with the next id and returns the id.
+    pub fn append(&self, mut record: RunRecord) -> Result<u64> {
+        record.id = self.records()?.last().map(|last| last.id + 1).unwrap_or(1);
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir).context(format!("Could not create {:?}", dir))?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .context(format!("Could not open history {:?}", self.path))?;
+        let line = serde_json::to_string(&record).context("Could not serialize run")?;
+        writeln!(file, "{}", line).context(format!("Could not write history {:?}", self.path))?;
+        Ok(record.id)
+    }
+}
+
+/// Records every job of a run in the [`History`].
+pub struct HistorySink {
+    history: History,
+    file: PathBuf,
+    /// the arguments of the jobs in the order they are run
+    args: Vec<BTreeMap<String, String>>,
+    inputs: Vec<(String, String)>,
+    user: Option<String>,
+    current: Option<RunRecord>,
+    jobs: usize,
+}
+
+impl HistorySink {
+    pub fn new(
+        history: History,
+        file: PathBuf,
+        args: Vec<BTreeMap<String, String>>,
+        inputs: Vec<(String, String)>,
+    ) -> Self {
+        let user = std::env::var("USER")
+            .or_else(|_| std::env::var("USERNAME"))
+            .ok();
+        Self {
+            history,
+            file,
+            args,
+            inputs,
+            user,
+            current: None,
+            jobs: 0,
+        }
+    }
+}
+
+impl EventSink for HistorySink {
+    fn handle(&mut self, record: &EventRecord) -> Result<()> {
+        match &record.event {
+            Event::JobStarted { job } => {
+                let args = self.args.get(self.jobs).cloned().unwrap_or_default();
+                self.jobs += 1;
+                // secrets could be given as arguments or inputs
+                self.current = Some(RunRecord {
+                    id: 0,
+                    file: self.file.clone(),
+                    job: job.clone(),
+                    user: self.user.clone(),
+                    args: args
+                        .into_iter()
+                        .map(|(name, value)| (name, mask::mask(&value)))
+                        .collect(),
+                    inputs: self
+                        .inputs
+                        .iter()
+                        .map(|(prompt, answer)| (prompt.clone(), mask::mask(answer)))
+                        .collect(),
+                    started_ms: record.timestamp_ms,
+                    finished_ms: record.timestamp_ms,
+                    outcome: Outcome::Success,
+                    failed_task: None,
+                    error: Vec::new(),
+                });
+            }
+            Event::TaskFailed { path, error, .. } => {
+                // the innermost task fails first
+                if let Some(current) = self.current.as_mut() {
+                    if current.failed_task.is_none() {
+                        current.failed_task = Some(path.clone());
+                        current.error = error.iter().map(|cause| mask::mask(cause)).collect();
+                    }
+                }
+            }
+            Event::JobFinished { success, .. } => {
+                if let Some(mut current) = self.current.take() {
+                    current.finished_ms = record.timestamp_ms;
+                    if !success {
+                        current.outcome = Outcome::Failed;
+                    }
+                    self.history.append(current)?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+/// Formats milliseconds since the unix epoch as local time.
+pub fn format_time(timestamp_ms: u64) -> String {
+    match Local.timestamp_millis_opt(timestamp_ms as i64).single() {
+        Some(time) => time.format("%Y-%m-%d %H:%M:%S").to_string(),
+        None => timestamp_ms.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{History, HistorySink, Outcome};
+    use crate::events::{Event, EventRecord, EventSink};
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn record_runs_test() {
+        let dir = std::env::temp_dir().join(format!("workflowo-history-{}", std::process::id()));
+        let path = dir.join("history.jsonl");
+
+        let mut args = BTreeMap::new();
+        args.insert("host".to_string(), "a".to_string());
+        let mut sink = HistorySink::new(
+            History::new(path.clone()),
+            "workflowo.yaml".into(),
+            vec![BTreeMap::new(), args],
+            vec![("Version".to_string(), "1.2".to_string())],
+        );
+        let events = vec![
+            Event::JobStarted {
+                job: "build".to_string(),
+            },
+            Event::JobFinished {
+                job: "build".to_string(),
+                duration_ms: 10,
+                success: true,
+            },
+            Event::JobStarted {
+                job: "deploy".to_string(),
+            },
+            Event::TaskFailed {
+                path: "1.0".to_string(),
+                task: "bash: false".to_string(),
+                duration_ms: 1,
+                output: Default::default(),
+                error: vec!["failed".to_string()],
+            },
+            Event::TaskFailed {
+                path: "1".to_string(),
+                task: "job upload".to_string(),
+                duration_ms: 1,
+                output: Default::default(),
+                error: vec!["Child 0 failed".to_string(), "failed".to_string()],
+            },
+            Event::JobFinished {
+                job: "deploy".to_string(),
+                duration_ms: 10,
+                success: false,
+            },
+        ];
+        for (index, event) in events.into_iter().enumerate() {
+            sink.handle(&EventRecord {
+                timestamp_ms: index as u64,
+                event,
+            })
+            .unwrap();
+        }
+
+        let records = History::new(path).records().unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].id, 1);
+        assert_eq!(records[0].outcome, Outcome::Success);
+        assert_eq!(records[1].id, 2);
+        assert_eq!(records[1].job, "deploy");
+        assert_eq!(records[1].args["host"], "a");
+        assert_eq!(records[1].inputs[0].1, "1.2");
+        assert_eq!(records[1].outcome, Outcome::Failed);
+        assert_eq!(records[1].failed_task.as_deref(), Some("1.0"));
+        assert_eq!((records[1].started_ms, records[1].finished_ms), (2, 5));
+    }
+}
@@ -1,3 +1,13 @@
+pub mod checkpoint;
 pub mod cli;
+pub mod events;
+pub mod history;
+pub mod logs;
+pub mod mask;
+pub mod report;
+pub mod secrets;
+pub mod signals;
 pub mod tasks;
+pub mod util;
+pub mod watch;
 pub mod yaml_parser;
@@ -0,0 +1,288 @@
+use crate::events::{Event, EventRecord, EventSink, TaskOutput};
+use crate::mask;
+use anyhow::{Context, Result};
+use chrono::{Local, TimeZone};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// Where the log files of runs are written to and how long they are kept.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogConfig {
+    pub dir: PathBuf,
+    /// the maximum number of log files, including the new one
+    pub keep: Option<usize>,
+    /// log files older than this are removed
+    pub max_age: Option<Duration>,
+}
+
+impl LogConfig {
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            keep: None,
+            max_age: None,
+        }
+    }
+}
+
+const LOG_FILE_PREFIX: &str = "run-";
+const LOG_FILE_EXTENSION: &str = ".log";
+
+/// Writes every event of a run into a timestamped log file. Secrets are masked.
+pub struct LogSink {
+    path: PathBuf,
+    writer: BufWriter<File>,
+}
+
+impl LogSink {
+    /// Creates the log file of this run and removes old log files.
+    pub fn create(config: &LogConfig) -> Result<Self> {
+        std::fs::create_dir_all(&config.dir)
+            .context(format!("Could not create log directory {:?}", config.dir))?;
+
+        let name = format!(
+            "{}{}{}",
+            LOG_FILE_PREFIX,
+            Local::now().format("%Y%m%d-%H%M%S%.3f"),
+            LOG_FILE_EXTENSION
+        );
+        let path = config.dir.join(name);
+        let file = File::create(&path).context(format!("Could not create log file {:?}", path))?;
+
+        rotate(config, &path).context("Could not remove old log files")?;
+
+        Ok(Self {
+            path,
+            writer: BufWriter::new(file),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+/// Removes the log files that are too old or exceed the count, except the current one.
+fn rotate(config: &LogConfig, current: &Path) -> Result<()> {
+    if config.keep.is_none() && config.max_age.is_none() {
+        return Ok(());
+    }
+
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(&config.dir)? {
+        let path = entry?.path();
+        let is_log = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| {
+                name.starts_with(LOG_FILE_PREFIX) && name.ends_with(LOG_FILE_EXTENSION)
+            });
+        if is_log && path != current {
+            files.push(path);
+        }
+    }
+    // the names contain the time, so the newest are first after sorting
+    files.sort();
+    files.reverse();
+
+    for (index, path) in files.into_iter().enumerate() {
+        let too_many = config.keep.is_some_and(|keep| index + 1 >= keep);
+        let too_old = match config.max_age {
+            Some(max_age) => std::fs::metadata(&path)
+                .and_then(|metadata| metadata.modified())
+                .ok()
+                .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+                .is_some_and(|age| age > max_age),
+            None => false,
+        };
+        if too_many || too_old {
+            std::fs::remove_file(&path).context(format!("Could not remove {:?}", path))?;
+        }
+    }
+    Ok(())
+}
+
+/// Formats the event as lines of the log file.
+fn format_record(record: &EventRecord) -> String {
+    let time = match Local
+        .timestamp_millis_opt(record.timestamp_ms as i64)
+        .single()
+    {
+        Some(time) => time.format("%Y-%m-%d %H:%M:%S%.3f").to_string(),
+        None => record.timestamp_ms.to_string(),
+    };
+
+    let text = match &record.event {
+        Event::JobStarted { job } => format!("{} job {} started\n", time, job),
+        Event::JobFinished {
+            job,
+            duration_ms,
+            success,
+        } => format!(
+            "{} job {} {} after {}ms\n",
+            time,
+            job,
+            if *success { "finished" } else { "failed" },
+            duration_ms
+        ),
+        Event::TaskStarted { path, task } => format!("{} task {} started: {}\n", time, path, task),
+        Event::TaskFinished {
+            path,
+            task,
+            duration_ms,
+            output,
+            ..
+        } => {
+            format!(
+                "{} task {} finished after {}ms: {}\n",
+                time, path, duration_ms, task
+            ) + &format_output(output)
+        }
+        Event::TaskSkipped { path, task, reason } => {
+            format!("{} task {} skipped ({}): {}\n", time, path, reason, task)
+        }
+        Event::TaskFailed {
+            path,
+            task,
+            duration_ms,
+            output,
+            error,
+        } => {
+            let mut text = format!(
+                "{} task {} failed after {}ms: {}\n",
+                time, path, duration_ms, task
+            ) + &format_output(output);
+            for (index, cause) in error.iter().enumerate() {
+                let label = if index == 0 { "error" } else { "caused by" };
+                text += &indent(label, cause);
+            }
+            text
+        }
+        Event::TaskApproved {
+            path,
+            task,
+            approved_by,
+        } => format!(
+            "{} task {} approved by {}: {}\n",
+            time, path, approved_by, task
+        ),
+    };
+    mask::mask(&text)
+}
+
+fn format_output(output: &TaskOutput) -> String {
+    let mut text = String::new();
+    if let Some(exit_code) = output.exit_code {
+        text += &format!("    exit code: {}\n", exit_code);
+    }
+    if let Some(stdout) = &output.stdout {
+        text += &indent("stdout", stdout);
+    }
+    if let Some(stderr) = &output.stderr {
+        text += &indent("stderr", stderr);
+    }
+    text
+}
+
+/// Prefixes every line of the text with the label.
+fn indent(label: &str, text: &str) -> String {
+    text.lines()
+        .map(|line| format!("    {} | {}\n", label, line))
+        .collect()
+}
+
+impl EventSink for LogSink {
+    fn handle(&mut self, record: &EventRecord) -> Result<()> {
+        self.writer
+            .write_all(format_record(record).as_bytes())
+            .context(format!("Could not write log file {:?}", self.path))?;
+        // flushed after every event, so the log is complete if the process is killed
+        self.writer
+            .flush()
+            .context(format!("Could not write log file {:?}", self.path))
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.writer
+            .flush()
+            .context(format!("Could not write log file {:?}", self.path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_record, rotate, LogConfig};
+    use crate::events::{Event, EventRecord, TaskOutput};
+    use crate::mask;
+
+    #[test]
+    fn format_failed_task_test() {
+        mask::register("log-test-secret");
+        let record = EventRecord {
+            timestamp_ms: 0,
+            event: Event::TaskFailed {
+                path: "1.0".to_string(),
+                task: "bash: false".to_string(),
+                duration_ms: 3,
+                output: TaskOutput {
+                    exit_code: Some(1),
+                    stdout: Some("a\nlog-test-secret\n".to_string()),
+                    stderr: None,
+                },
+                error: vec!["Child 0 failed".to_string(), "command failed".to_string()],
+            },
+        };
+        let text = format_record(&record);
+        let lines: Vec<&str> = text.lines().collect();
+        assert!(lines[0].ends_with(" task 1.0 failed after 3ms: bash: false"));
+        assert_eq!(
+            lines[1..],
+            [
+                "    exit code: 1",
+                "    stdout | a",
+                &format!("    stdout | {}", mask::MASK),
+                "    error | Child 0 failed",
+                "    caused by | command failed",
+            ]
+        );
+    }
+
+    #[test]
+    fn rotate_test() {
+        let dir = std::env::temp_dir().join(format!("workflowo-logs-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for name in [
+            "run-20240101-000000.000.log",
+            "run-20240102-000000.000.log",
+            "run-20240103-000000.000.log",
+            "other.txt",
+        ] {
+            std::fs::write(dir.join(name), "").unwrap();
+        }
+
+        let config = LogConfig {
+            dir: dir.clone(),
+            keep: Some(2),
+            max_age: None,
+        };
+        rotate(&config, &dir.join("run-20240103-000000.000.log")).unwrap();
+
+        let mut names: Vec<String> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        names.sort();
+        assert_eq!(
+            names,
+            [
+                "other.txt",
+                "run-20240102-000000.000.log",
+                "run-20240103-000000.000.log"
+            ]
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
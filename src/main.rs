@@ -1,6 +1,21 @@
-use workflowo::cli;
-use workflowo::tasks::Task;
-use workflowo::yaml_parser;
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use workflowo::checkpoint::{CheckpointSink, CheckpointStore};
+use workflowo::cli::{self, Command, ExitCode, HistoryCommand, JobCall, Output, SecretsCommand};
+use workflowo::events::JsonLinesSink;
+use workflowo::history::{self, History, HistorySink, Outcome};
+use workflowo::logs::{LogConfig, LogSink};
+use workflowo::mask;
+use workflowo::report::ReportSink;
+use workflowo::secrets::{self, SecretsFile};
+use workflowo::signals;
+use workflowo::tasks::{self, CancelToken, Job, RunContext, Selection, Task};
+use workflowo::watch::{self, Watcher};
+use workflowo::yaml_parser::{self, Workflow};
 
 pub fn error_chain_string(error: anyhow::Error) -> String {
     let mut message = String::new();
@@ -13,31 +28,560 @@ pub fn error_chain_string(error: anyhow::Error) -> String {
         message += &format!("\nCaused by:\n\t{}", err);
     }
 
-    message
+    mask::mask(&message)
+}
+
+/// Prints the error to stderr, so it is not mixed with json events, and exits with the code.
+fn fail(error: anyhow::Error, code: ExitCode) -> ! {
+    eprintln!("{}", error_chain_string(error));
+    code.exit()
+}
+
+/// Parses the jobs of the calls. Exits if a job is not found or can not be parsed.
+fn parse_jobs(workflow: &Workflow, values: &[String]) -> Vec<Job> {
+    let calls = match cli::parse_job_calls(values) {
+        Ok(x) => x,
+        Err(err) => fail(err, ExitCode::InvalidArguments),
+    };
+
+    let mut jobs = Vec::new();
+    for JobCall { name, args } in calls {
+        match workflow.job(&name, &args) {
+            Ok(Some(job)) => jobs.push(job),
+            Ok(None) => {
+                eprintln!("Error! Job {} not found.", name);
+                ExitCode::JobNotFound.exit();
+            }
+            Err(err) => fail(err, ExitCode::InvalidWorkflow),
+        }
+    }
+    jobs
 }
 
 fn main() {
-    let args = cli::parse_and_validate_args();
+    let args = cli::parse_args();
+
+    // the history does not need a workflow file
+    if let Command::History {
+        command,
+        job,
+        outcome,
+        limit,
+    } = args.command
+    {
+        if let Err(error) = show_history(command, job, outcome, limit) {
+            fail(error, ExitCode::InvalidArguments);
+        }
+        return;
+    }
+    if let Command::Secrets { command } = args.command {
+        if let Err(error) = manage_secrets(command) {
+            fail(error, ExitCode::InvalidArguments);
+        }
+        return;
+    }
 
-    let jobs = match yaml_parser::jobs_from_file(args.file) {
+    let file = cli::workflow_file(&args);
+    let workflow = match yaml_parser::Workflow::from_file(file.clone()) {
         Ok(x) => x,
-        Err(err) => {
-            println!("{}", error_chain_string(err));
-            std::process::exit(1);
+        Err(err) => fail(err, ExitCode::InvalidWorkflow),
+    };
+
+    match args.command {
+        Command::List => {
+            let infos = workflow.job_infos();
+            let width = infos.iter().map(|info| info.name.len()).max().unwrap_or(0);
+            for info in infos {
+                let mut line = format!("{:width$}", info.name, width = width);
+                if !info.params.is_empty() {
+                    line += &format!("  [{}]", info.params.join(", "));
+                }
+                if let Some(description) = info.description {
+                    line += &format!("  {}", description);
+                }
+                println!("{}", line.trim_end());
+            }
+        }
+        Command::Describe { job } => {
+            for job in parse_jobs(&workflow, &job) {
+                if args.verbose {
+                    println!("{}", mask::mask(&job.to_string()));
+                }
+                if let Err(error) = job.dry_run(&mut RunContext::default(), 0) {
+                    fail(error, ExitCode::InvalidWorkflow);
+                }
+            }
+        }
+        Command::Validate => {
+            let (errors, not_parsed) = workflow.validate();
+            for name in not_parsed {
+                println!(
+                    "Warning: Job {} has required params, only its params were validated",
+                    name
+                );
+            }
+            if !errors.is_empty() {
+                let count = errors.len();
+                for error in errors {
+                    println!("{}", error_chain_string(error));
+                }
+                println!("{} errors found", count);
+                ExitCode::InvalidWorkflow.exit();
+            }
+            println!("No errors found");
+        }
+        Command::Run {
+            jobs,
+            dry_run,
+            output,
+            report,
+            log_dir,
+            no_history,
+            resume,
+            from,
+            only,
+            force,
+            yes,
+        } => {
+            let calls = cli::parse_job_calls(&jobs).unwrap_or_default();
+            let jobs = parse_jobs(&workflow, &jobs);
+            let file = file.canonicalize().unwrap_or(file);
+            let selection = match (resume, from, only) {
+                (true, _, _) => Selection::Resume(HashSet::new()),
+                (_, Some(path), _) => match tasks::parse_task_path(&path) {
+                    Ok(path) => Selection::From(path),
+                    Err(err) => fail(err, ExitCode::InvalidArguments),
+                },
+                (_, _, Some(path)) => match tasks::parse_task_path(&path) {
+                    Ok(path) => Selection::Only(path),
+                    Err(err) => fail(err, ExitCode::InvalidArguments),
+                },
+                _ => Selection::All,
+            };
+            if selection != Selection::All && jobs.len() != 1 {
+                eprintln!("Error! --resume, --from and --only need exactly one job.");
+                ExitCode::InvalidArguments.exit();
+            }
+            let mut context = RunContext::default();
+            context.selection = selection;
+            context.force = force;
+            context.approve = yes;
+            context.verbose = args.verbose;
+            match output {
+                Output::Text => {}
+                Output::Json(None) => {
+                    context.events.add_sink(Box::new(JsonLinesSink::stdout()));
+                    context.messages_to_stderr = true;
+                }
+                Output::Json(Some(path)) => match JsonLinesSink::file(&path) {
+                    Ok(sink) => context.events.add_sink(Box::new(sink)),
+                    Err(err) => fail(err, ExitCode::InvalidArguments),
+                },
+            }
+            if !report.is_empty() {
+                context.events.add_sink(Box::new(ReportSink::new(report)));
+            }
+            let log_config = match (workflow.log_config(), log_dir) {
+                (Ok(config), None) => config,
+                (Ok(config), Some(dir)) => Some(match config {
+                    Some(config) => LogConfig { dir, ..config },
+                    None => LogConfig::new(dir),
+                }),
+                (Err(err), _) => fail(err.context("Error in LOG"), ExitCode::InvalidWorkflow),
+            };
+            if let (Some(log_config), false) = (log_config, dry_run) {
+                match LogSink::create(&log_config) {
+                    Ok(sink) => {
+                        if args.verbose {
+                            context.message(&format!("Writing log to {:?}", sink.path()));
+                        }
+                        context.events.add_sink(Box::new(sink));
+                    }
+                    Err(err) => fail(err, ExitCode::InvalidArguments),
+                }
+            }
+
+            if !dry_run && !no_history {
+                match History::open() {
+                    Ok(history) => {
+                        let job_args = calls
+                            .into_iter()
+                            .map(|call| {
+                                call.args
+                                    .into_iter()
+                                    .map(|(name, value)| {
+                                        let value = match value {
+                                            serde_yaml::Value::String(value) => value,
+                                            other => format!("{:?}", other),
+                                        };
+                                        (name, value)
+                                    })
+                                    .collect::<BTreeMap<String, String>>()
+                            })
+                            .collect();
+                        context.events.add_sink(Box::new(HistorySink::new(
+                            history,
+                            file.clone(),
+                            job_args,
+                            workflow.inputs().to_vec(),
+                        )));
+                    }
+                    Err(error) => eprintln!("Warning: the run is not recorded: {:#}", error),
+                }
+            }
+
+            if !dry_run {
+                if let Err(error) = record_checkpoints(&mut context, &file, &jobs) {
+                    if context.selection != Selection::All {
+                        fail(error, ExitCode::InvalidArguments);
+                    }
+                    eprintln!("Warning: no checkpoint is recorded: {:#}", error);
+                }
+            }
+
+            let cancel = context.cancel.clone();
+            if let Err(error) =
+                signals::on_termination(move |count| signals::cancel_run(count, &cancel))
+            {
+                eprintln!("Warning: {:#}", error);
+            }
+
+            for job in jobs {
+                if args.verbose {
+                    context.message(&job.to_string());
+                }
+                if dry_run {
+                    if let Err(error) = job.dry_run(&mut context, 0) {
+                        fail(error, ExitCode::TaskFailed);
+                    }
+                    continue;
+                }
+                context.message(&format!("Executing Job {}", job.name));
+                if let Err(error) = job.run(&mut context) {
+                    finish_events(&context);
+                    context.message(&error_chain_string(error));
+                    if context.cancel.is_cancelled() {
+                        ExitCode::Cancelled.exit();
+                    }
+                    ExitCode::TaskFailed.exit();
+                }
+            }
+            finish_events(&context);
         }
+        Command::Watch {
+            jobs,
+            paths,
+            debounce,
+        } => watch(
+            file,
+            workflow,
+            &jobs,
+            paths,
+            Duration::from_millis(debounce),
+            args.verbose,
+        ),
+        Command::History { .. } => unreachable!("the history is shown before"),
+        Command::Secrets { .. } => unreachable!("the secrets are managed before"),
+    }
+}
+
+/// Executes the jobs whenever a watched file changes. A run that is in progress is cancelled.
+/// The workflow is reloaded when its file changes.
+fn watch(
+    file: PathBuf,
+    workflow: Workflow,
+    values: &[String],
+    patterns: Vec<String>,
+    debounce: Duration,
+    verbose: bool,
+) {
+    let calls = match cli::parse_job_calls(values) {
+        Ok(calls) => calls,
+        Err(err) => fail(err, ExitCode::InvalidArguments),
     };
-    for job in &jobs {
-        if args.verbose {
-            println!("{}", job);
+    let file = file.canonicalize().unwrap_or(file);
+    // the workflow file and the files it includes
+    let mut files = workflow.files().to_vec();
+    let watcher = match Watcher::new(patterns, files.clone()) {
+        Ok(watcher) => watcher,
+        Err(err) => fail(err, ExitCode::InvalidArguments),
+    };
+
+    // the watcher cancels the run that is in progress
+    let current_cancel = Arc::new(Mutex::new(CancelToken::default()));
+    let (sender, receiver) = mpsc::channel();
+    let watcher_cancel = current_cancel.clone();
+    let watcher_sender = sender.clone();
+    thread::spawn(move || {
+        watcher.run(watcher_sender, move || {
+            watcher_cancel.lock().unwrap().cancel()
+        })
+    });
+
+    // a signal stops watching after the run that is in progress
+    let stopped = Arc::new(AtomicBool::new(false));
+    let signal_cancel = current_cancel.clone();
+    let signal_stopped = stopped.clone();
+    let handler = signals::on_termination(move |count| {
+        signal_stopped.store(true, Ordering::SeqCst);
+        signals::cancel_run(count, &signal_cancel.lock().unwrap());
+        let _ = sender.send(Vec::new());
+    });
+    if let Err(error) = handler {
+        eprintln!("Warning: {:#}", error);
+    }
+
+    let mut workflow = Some(workflow);
+    loop {
+        if let Some(workflow) = &workflow {
+            let mut context = RunContext::default();
+            context.verbose = verbose;
+            context.cancel = CancelToken::default();
+            *current_cancel.lock().unwrap() = context.cancel.clone();
+            if let Err(error) = run_calls(workflow, &calls, &mut context) {
+                if context.cancel.is_cancelled() && !stopped.load(Ordering::SeqCst) {
+                    println!("Run cancelled, because files changed");
+                } else {
+                    eprintln!("{}", error_chain_string(error));
+                }
+            }
+        }
+        if stopped.load(Ordering::SeqCst) {
+            ExitCode::Cancelled.exit();
+        }
+        println!("Waiting for changes...");
+
+        let changed = watch::wait_for_changes(&receiver, debounce);
+        if stopped.load(Ordering::SeqCst) {
+            ExitCode::Cancelled.exit();
+        }
+        if verbose {
+            for path in &changed {
+                println!("Changed: {}", path.display());
+            }
+        }
+        if changed.iter().any(|path| files.contains(path)) {
+            println!("Reloading {}", file.display());
+            workflow = match Workflow::from_file(file.clone()) {
+                Ok(workflow) => {
+                    files = workflow.files().to_vec();
+                    Some(workflow)
+                }
+                Err(error) => {
+                    eprintln!("{}", error_chain_string(error));
+                    None
+                }
+            };
+        }
+    }
+}
+
+/// Parses and runs the jobs of the calls in order.
+fn run_calls(
+    workflow: &Workflow,
+    calls: &[JobCall],
+    context: &mut RunContext,
+) -> anyhow::Result<()> {
+    for call in calls {
+        let job = match workflow.job(&call.name, &call.args)? {
+            Some(job) => job,
+            None => anyhow::bail!(format!("Job {} not found", call.name)),
+        };
+        context.message(&format!("Executing Job {}", job.name));
+        job.run(context)?;
+    }
+    Ok(())
+}
+
+/// Records the completed tasks of the jobs. A run with a selection continues the checkpoint of the
+/// last run, so `--resume` also skips the tasks that were selected before.
+fn record_checkpoints(context: &mut RunContext, file: &Path, jobs: &[Job]) -> anyhow::Result<()> {
+    let store = CheckpointStore::open()?;
+    let mut resumed = Vec::new();
+    if context.selection != Selection::All {
+        for job in jobs {
+            resumed.push(store.load(file, &job.name)?);
+        }
+    }
+    if let (Selection::Resume(completed), Some(checkpoint)) =
+        (&mut context.selection, resumed.first())
+    {
+        completed.clone_from(&checkpoint.completed);
+    }
+    context.events.add_sink(Box::new(CheckpointSink::new(
+        store,
+        file.to_path_buf(),
+        resumed,
+    )));
+    Ok(())
+}
+
+/// Lists, sets, gets or edits the secrets in the secrets file.
+fn manage_secrets(command: SecretsCommand) -> anyhow::Result<()> {
+    let file = SecretsFile::open()?;
+    if !file.exists() && !matches!(command, SecretsCommand::Set { .. }) {
+        anyhow::bail!(format!(
+            "There is no secrets file {:?}, add secrets with `workflowo secrets set`",
+            secrets::secrets_file()?
+        ));
+    }
+    let passphrase = secrets::passphrase(!file.exists())?;
+    let mut values = file.load(&passphrase)?;
+
+    match command {
+        SecretsCommand::Set { name, value } => {
+            let value = match value {
+                Some(value) => value,
+                None => rpassword::prompt_password(format!("Value of {}: ", name))?,
+            };
+            values.insert(name.clone(), value);
+            file.save(&passphrase, &values)?;
+            println!("Saved secret {}", name);
+        }
+        SecretsCommand::Get { name } => match values.get(&name) {
+            Some(value) => println!("{}", value),
+            None => anyhow::bail!(format!("Secret {} does not exist", name)),
+        },
+        SecretsCommand::List => {
+            for name in values.keys() {
+                println!("{}", name);
+            }
+        }
+        SecretsCommand::Edit => {
+            let edited = edit_secrets(&values)?;
+            file.save(&passphrase, &edited)?;
+            println!("Saved {} secrets", edited.len());
+        }
+    }
+    Ok(())
+}
+
+/// Opens the secrets as yaml in the editor of the user and returns the edited secrets.
+fn edit_secrets(values: &BTreeMap<String, String>) -> anyhow::Result<BTreeMap<String, String>> {
+    use anyhow::Context;
+    use std::io::Write;
+
+    let path = history::data_dir()?.join(format!("secrets-{}.yaml", std::process::id()));
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    std::fs::create_dir_all(history::data_dir()?)?;
+    let mut temp = options
+        .open(&path)
+        .context(format!("Could not create {:?}", path))?;
+    temp.write_all(serde_yaml::to_string(values)?.as_bytes())?;
+    drop(temp);
+
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| match cfg!(windows) {
+            true => "notepad".to_string(),
+            false => "vi".to_string(),
+        });
+    // the editor can have arguments, e.g. `code --wait`
+    let mut words = editor.split_whitespace();
+    let status = std::process::Command::new(words.next().unwrap_or("vi"))
+        .args(words)
+        .arg(&path)
+        .status();
+    let edited = std::fs::read_to_string(&path);
+    let _ = std::fs::remove_file(&path);
+
+    let status = status.context(format!("Could not start the editor {}", editor))?;
+    if !status.success() {
+        anyhow::bail!(format!(
+            "The editor exited with {}, nothing was saved",
+            status
+        ));
+    }
+    let edited = edited.context(format!("Could not read {:?}", path))?;
+    serde_yaml::from_str(&edited).context("The secrets are not a map of names to strings")
+}
+
+/// Prints the runs of the history or the details of one run.
+fn show_history(
+    command: Option<HistoryCommand>,
+    job: Option<String>,
+    outcome: Option<Outcome>,
+    limit: usize,
+) -> anyhow::Result<()> {
+    let records = History::open()?.records()?;
+
+    if let Some(HistoryCommand::Show { id }) = command {
+        let record = match records.into_iter().find(|record| record.id == id) {
+            Some(record) => record,
+            None => anyhow::bail!(format!("Run {} not found in the history", id)),
+        };
+        println!("Run {}", record.id);
+        println!("File:        {}", record.file.display());
+        println!("Job:         {}", record.job);
+        println!("User:        {}", record.user.unwrap_or_default());
+        println!("Started:     {}", history::format_time(record.started_ms));
+        println!(
+            "Finished:    {} ({}s)",
+            history::format_time(record.finished_ms),
+            record.finished_ms.saturating_sub(record.started_ms) as f64 / 1000.0
+        );
+        println!(
+            "Outcome:     {}",
+            format!("{:?}", record.outcome).to_lowercase()
+        );
+        if let Some(failed_task) = record.failed_task {
+            println!("Failed task: {}", failed_task);
+        }
+        if !record.args.is_empty() {
+            println!("Arguments:");
+            for (name, value) in record.args {
+                println!("    {}={}", name, value);
+            }
+        }
+        if !record.inputs.is_empty() {
+            println!("Inputs:");
+            for (prompt, answer) in record.inputs {
+                println!("    {}: {}", prompt, answer);
+            }
         }
-        if job.name == args.job {
-            println!("Executing Job {}", job.name);
-            if let Err(error) = job.execute() {
-                println!("{}", error_chain_string(error));
-                std::process::exit(1);
+        for (index, cause) in record.error.iter().enumerate() {
+            if index == 0 {
+                println!("Error: {}", cause.trim_end());
+            } else {
+                println!("Caused by:\n\t{}", cause.trim_end());
             }
-            return;
         }
+        return Ok(());
+    }
+
+    let records: Vec<_> = records
+        .into_iter()
+        .filter(|record| job.as_ref().is_none_or(|job| &record.job == job))
+        .filter(|record| outcome.is_none_or(|outcome| record.outcome == outcome))
+        .collect();
+    let skip = records.len().saturating_sub(limit);
+    let width = records
+        .iter()
+        .map(|record| record.job.len())
+        .max()
+        .unwrap_or(0);
+    for record in records.into_iter().skip(skip) {
+        let line = format!(
+            "{:>4}  {}  {:width$}  {:7}  {:>8.1}s  {}",
+            record.id,
+            history::format_time(record.started_ms),
+            record.job,
+            format!("{:?}", record.outcome).to_lowercase(),
+            record.finished_ms.saturating_sub(record.started_ms) as f64 / 1000.0,
+            record.user.unwrap_or_default(),
+            width = width
+        );
+        println!("{}", line.trim_end());
+    }
+    Ok(())
+}
+
+/// Finishes the sinks of the events. Errors are only printed, because the run is already over.
+fn finish_events(context: &RunContext) {
+    if let Err(error) = context.events.finish() {
+        eprintln!("{}", error_chain_string(error));
     }
-    eprintln!("Error! Job {} not found.", args.job);
 }
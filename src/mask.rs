@@ -0,0 +1,46 @@
+use std::sync::Mutex;
+
+/// The text that is shown instead of a secret.
+pub const MASK: &str = "***Not displayed for security reasons***";
+
+/// All secrets that are known in this process.
+static SECRETS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// Registers a value that must not be shown, e.g. a password or hidden input.
+pub fn register(secret: &str) {
+    if secret.is_empty() {
+        return;
+    }
+    let mut secrets = SECRETS.lock().unwrap();
+    if !secrets.iter().any(|known| known == secret) {
+        secrets.push(secret.to_string());
+        // longer secrets first, so a secret that contains another one is masked completely
+        secrets.sort_by_key(|known| std::cmp::Reverse(known.len()));
+    }
+}
+
+/// Replaces all registered secrets in the text.
+pub fn mask(text: &str) -> String {
+    let mut masked = text.to_string();
+    for secret in SECRETS.lock().unwrap().iter() {
+        masked = masked.replace(secret.as_str(), MASK);
+    }
+    masked
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{mask, register, MASK};
+
+    #[test]
+    fn mask_test() {
+        register("mask-test-secret");
+        register("mask-test-secret-longer");
+        register("");
+        assert_eq!(
+            mask("a mask-test-secret-longer b mask-test-secret"),
+            format!("a {} b {}", MASK, MASK)
+        );
+        assert_eq!(mask("nothing"), "nothing");
+    }
+}
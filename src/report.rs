@@ -0,0 +1,591 @@
+use crate::events::{Event, EventRecord, EventSink, TaskOutput};
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// The format of a report file.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReportFormat {
+    Junit,
+    Markdown,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Status {
+    Passed,
+    Failed,
+    Skipped,
+}
+
+impl Status {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Passed => "passed",
+            Self::Failed => "failed",
+            Self::Skipped => "skipped",
+        }
+    }
+}
+
+/// A task of a run with the tasks that were executed as its children.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TaskNode {
+    pub path: String,
+    pub task: String,
+    pub status: Status,
+    pub duration_ms: u64,
+    pub output: TaskOutput,
+    /// the error followed by its causes
+    pub error: Vec<String>,
+    /// skip reason
+    pub reason: Option<String>,
+    pub children: Vec<TaskNode>,
+}
+
+impl TaskNode {
+    /// Returns the number of test cases, failures and skipped test cases.
+    fn counts(&self) -> (usize, usize, usize) {
+        if self.children.is_empty() {
+            return match self.status {
+                Status::Passed => (1, 0, 0),
+                Status::Failed => (1, 1, 0),
+                Status::Skipped => (1, 0, 1),
+            };
+        }
+        let mut counts = (0, 0, 0);
+        for child in &self.children {
+            let (tests, failures, skipped) = child.counts();
+            counts = (counts.0 + tests, counts.1 + failures, counts.2 + skipped);
+        }
+        // the task failed itself and not because of a child
+        if self.failed_itself() {
+            counts = (counts.0 + 1, counts.1 + 1, counts.2);
+        }
+        counts
+    }
+
+    fn failed_itself(&self) -> bool {
+        self.status == Status::Failed
+            && !self.children.is_empty()
+            && self
+                .children
+                .iter()
+                .all(|child| child.status != Status::Failed)
+    }
+
+    fn name(&self) -> String {
+        format!("{} {}", self.path, self.task)
+    }
+}
+
+/// A job that was run with the tree of its tasks.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JobReport {
+    pub name: String,
+    pub success: Option<bool>,
+    pub duration_ms: u64,
+    pub tasks: Vec<TaskNode>,
+}
+
+impl JobReport {
+    fn counts(&self) -> (usize, usize, usize) {
+        let mut counts = (0, 0, 0);
+        for task in &self.tasks {
+            let (tests, failures, skipped) = task.counts();
+            counts = (counts.0 + tests, counts.1 + failures, counts.2 + skipped);
+        }
+        counts
+    }
+}
+
+/// Collects the events of a run and writes reports when the run is finished.
+pub struct ReportSink {
+    reports: Vec<(ReportFormat, PathBuf)>,
+    jobs: Vec<JobReport>,
+    /// the tasks of the current job in the order they were started
+    tasks: Vec<TaskNode>,
+    indices: HashMap<String, usize>,
+}
+
+impl ReportSink {
+    pub fn new(reports: Vec<(ReportFormat, PathBuf)>) -> Self {
+        Self {
+            reports,
+            jobs: Vec::new(),
+            tasks: Vec::new(),
+            indices: HashMap::new(),
+        }
+    }
+
+    fn finish_task(&mut self, path: &str, status: Status, duration_ms: u64, output: &TaskOutput) {
+        if let Some(index) = self.indices.get(path) {
+            let node = &mut self.tasks[*index];
+            node.status = status;
+            node.duration_ms = duration_ms;
+            node.output = output.clone();
+        }
+    }
+
+    /// Moves the collected tasks into the tree of the current job.
+    fn finish_job(&mut self) {
+        if self.tasks.is_empty() {
+            return;
+        }
+        let tasks = std::mem::take(&mut self.tasks);
+        self.indices.clear();
+        if let Some(job) = self.jobs.last_mut() {
+            job.tasks = build_tree(tasks);
+        }
+    }
+
+    fn write(&self, format: ReportFormat) -> String {
+        match format {
+            ReportFormat::Junit => junit(&self.jobs),
+            ReportFormat::Markdown => markdown(&self.jobs),
+        }
+    }
+}
+
+impl EventSink for ReportSink {
+    fn handle(&mut self, record: &EventRecord) -> Result<()> {
+        match &record.event {
+            Event::JobStarted { job } => {
+                self.finish_job();
+                self.jobs.push(JobReport {
+                    name: job.clone(),
+                    success: None,
+                    duration_ms: 0,
+                    tasks: Vec::new(),
+                });
+            }
+            Event::JobFinished {
+                duration_ms,
+                success,
+                ..
+            } => {
+                self.finish_job();
+                if let Some(job) = self.jobs.last_mut() {
+                    job.success = Some(*success);
+                    job.duration_ms = *duration_ms;
+                }
+            }
+            Event::TaskStarted { path, task } => {
+                self.indices.insert(path.clone(), self.tasks.len());
+                self.tasks.push(TaskNode {
+                    path: path.clone(),
+                    task: task.clone(),
+                    status: Status::Failed,
+                    duration_ms: 0,
+                    output: TaskOutput::default(),
+                    error: Vec::new(),
+                    reason: None,
+                    children: Vec::new(),
+                });
+            }
+            Event::TaskFinished {
+                path,
+                duration_ms,
+                output,
+                ..
+            } => self.finish_task(path, Status::Passed, *duration_ms, output),
+            Event::TaskFailed {
+                path,
+                duration_ms,
+                output,
+                error,
+                ..
+            } => {
+                self.finish_task(path, Status::Failed, *duration_ms, output);
+                if let Some(index) = self.indices.get(path) {
+                    self.tasks[*index].error = error.clone();
+                }
+            }
+            Event::TaskSkipped { path, task, reason } => {
+                self.indices.insert(path.clone(), self.tasks.len());
+                self.tasks.push(TaskNode {
+                    path: path.clone(),
+                    task: task.clone(),
+                    status: Status::Skipped,
+                    duration_ms: 0,
+                    output: TaskOutput::default(),
+                    error: Vec::new(),
+                    reason: Some(reason.clone()),
+                    children: Vec::new(),
+                });
+            }
+            Event::TaskApproved { .. } => {}
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.finish_job();
+        for (format, path) in &self.reports {
+            std::fs::write(path, self.write(*format))
+                .context(format!("Could not write report {:?}", path))?;
+        }
+        Ok(())
+    }
+}
+
+/// Builds the tree of tasks. The parent of a task is the task with the longest path that is a
+/// prefix of its path. Loops add path segments without tasks.
+fn build_tree(tasks: Vec<TaskNode>) -> Vec<TaskNode> {
+    let paths: HashMap<String, usize> = tasks
+        .iter()
+        .enumerate()
+        .map(|(index, task)| (task.path.clone(), index))
+        .collect();
+
+    let parents: Vec<Option<usize>> = tasks
+        .iter()
+        .map(|task| {
+            let mut path = task.path.as_str();
+            while let Some((parent, _)) = path.rsplit_once('.') {
+                if let Some(index) = paths.get(parent) {
+                    return Some(*index);
+                }
+                path = parent;
+            }
+            None
+        })
+        .collect();
+
+    // children are started after their parents, so attaching from the back keeps the order
+    let mut nodes: Vec<Option<TaskNode>> = tasks.into_iter().map(Some).collect();
+    for index in (0..nodes.len()).rev() {
+        if let Some(parent) = parents[index] {
+            let node = nodes[index].take().unwrap();
+            nodes[parent].as_mut().unwrap().children.insert(0, node);
+        }
+    }
+    nodes.into_iter().flatten().collect()
+}
+
+fn escape_xml(text: &str) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        match c {
+            '&' => escaped += "&amp;",
+            '<' => escaped += "&lt;",
+            '>' => escaped += "&gt;",
+            '"' => escaped += "&quot;",
+            '\'' => escaped += "&apos;",
+            // characters that are not allowed in xml
+            c if (c as u32) < 0x20 && c != '\n' && c != '\r' && c != '\t' => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn seconds(duration_ms: u64) -> String {
+    format!("{:.3}", duration_ms as f64 / 1000.0)
+}
+
+/// Renders the jobs as JUnit XML. Jobs and tasks with children are test suites.
+pub fn junit(jobs: &[JobReport]) -> String {
+    let (mut tests, mut failures, mut skipped, mut duration_ms) = (0, 0, 0, 0);
+    for job in jobs {
+        let counts = job.counts();
+        tests += counts.0;
+        failures += counts.1;
+        skipped += counts.2;
+        duration_ms += job.duration_ms;
+    }
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml += &format!(
+        "<testsuites name=\"workflowo\" tests=\"{}\" failures=\"{}\" skipped=\"{}\" time=\"{}\">\n",
+        tests,
+        failures,
+        skipped,
+        seconds(duration_ms)
+    );
+    for job in jobs {
+        let (tests, failures, skipped) = job.counts();
+        xml += &format!(
+            "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" skipped=\"{}\" time=\"{}\">\n",
+            escape_xml(&job.name),
+            tests,
+            failures,
+            skipped,
+            seconds(job.duration_ms)
+        );
+        for task in &job.tasks {
+            junit_task(&mut xml, &job.name, task, 2);
+        }
+        xml += "  </testsuite>\n";
+    }
+    xml += "</testsuites>\n";
+    xml
+}
+
+fn junit_task(xml: &mut String, classname: &str, task: &TaskNode, depth: usize) {
+    let indent = "  ".repeat(depth);
+    if task.children.is_empty() {
+        junit_testcase(xml, classname, task, depth);
+        return;
+    }
+
+    let (tests, failures, skipped) = task.counts();
+    *xml += &format!(
+        "{}<testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" skipped=\"{}\" time=\"{}\">\n",
+        indent,
+        escape_xml(&task.name()),
+        tests,
+        failures,
+        skipped,
+        seconds(task.duration_ms)
+    );
+    let child_classname = format!("{}.{}", classname, task.path);
+    for child in &task.children {
+        junit_task(xml, &child_classname, child, depth + 1);
+    }
+    if task.failed_itself() {
+        junit_testcase(xml, &child_classname, task, depth + 1);
+    }
+    *xml += &format!("{}</testsuite>\n", indent);
+}
+
+fn junit_testcase(xml: &mut String, classname: &str, task: &TaskNode, depth: usize) {
+    let indent = "  ".repeat(depth);
+    *xml += &format!(
+        "{}<testcase name=\"{}\" classname=\"{}\" time=\"{}\">\n",
+        indent,
+        escape_xml(&task.name()),
+        escape_xml(classname),
+        seconds(task.duration_ms)
+    );
+    match task.status {
+        Status::Failed => {
+            *xml += &format!(
+                "{}  <failure message=\"{}\">{}</failure>\n",
+                indent,
+                escape_xml(task.error.first().map_or("", |error| error.as_str())),
+                escape_xml(&task.error.join("\nCaused by:\n"))
+            );
+        }
+        Status::Skipped => {
+            *xml += &format!(
+                "{}  <skipped message=\"{}\"/>\n",
+                indent,
+                escape_xml(task.reason.as_deref().unwrap_or_default())
+            );
+        }
+        Status::Passed => {}
+    }
+    if let Some(stdout) = task.output.stdout.as_deref().filter(|out| !out.is_empty()) {
+        *xml += &format!(
+            "{}  <system-out>{}</system-out>\n",
+            indent,
+            escape_xml(stdout)
+        );
+    }
+    if let Some(stderr) = task.output.stderr.as_deref().filter(|err| !err.is_empty()) {
+        *xml += &format!(
+            "{}  <system-err>{}</system-err>\n",
+            indent,
+            escape_xml(stderr)
+        );
+    }
+    *xml += &format!("{}</testcase>\n", indent);
+}
+
+/// Renders the jobs as a Markdown document with a table of the tasks of every job.
+pub fn markdown(jobs: &[JobReport]) -> String {
+    let mut text = String::from("# Workflowo Report\n");
+    for job in jobs {
+        let (tests, failures, skipped) = job.counts();
+        text += &format!(
+            "\n## Job {} - {} ({}s)\n\n{} tasks, {} failed, {} skipped\n\n",
+            job.name,
+            match job.success {
+                Some(true) => "passed",
+                Some(false) => "failed",
+                None => "unfinished",
+            },
+            seconds(job.duration_ms),
+            tests,
+            failures,
+            skipped
+        );
+        text += "| Path | Task | Status | Duration | Exit code |\n";
+        text += "|------|------|--------|----------|-----------|\n";
+        let mut details = String::new();
+        for task in &job.tasks {
+            markdown_task(&mut text, &mut details, task, 0);
+        }
+        text += &details;
+    }
+    text
+}
+
+fn markdown_task(text: &mut String, details: &mut String, task: &TaskNode, depth: usize) {
+    *text += &format!(
+        "| {} | {}{} | {} | {}s | {} |\n",
+        task.path,
+        "&nbsp;&nbsp;".repeat(depth * 2),
+        task.task.replace('|', "\\|").replace('\n', " "),
+        task.status.as_str(),
+        seconds(task.duration_ms),
+        task.output
+            .exit_code
+            .map(|code| code.to_string())
+            .unwrap_or_default()
+    );
+
+    // the error is only shown at the task that caused it
+    let show_error = task.status == Status::Failed
+        && task
+            .children
+            .iter()
+            .all(|child| child.status != Status::Failed);
+    let has_output = task
+        .output
+        .stdout
+        .as_deref()
+        .is_some_and(|out| !out.is_empty())
+        || task
+            .output
+            .stderr
+            .as_deref()
+            .is_some_and(|err| !err.is_empty());
+    if show_error || has_output {
+        *details += &format!("\n### {} {}\n", task.path, task.task);
+        if show_error {
+            *details += &format!("\n```\n{}\n```\n", task.error.join("\nCaused by:\n"));
+        }
+        for (name, output) in [
+            ("stdout", &task.output.stdout),
+            ("stderr", &task.output.stderr),
+        ] {
+            if let Some(output) = output.as_deref().filter(|output| !output.is_empty()) {
+                *details += &format!(
+                    "\n<details><summary>{}</summary>\n\n```\n{}\n```\n\n</details>\n",
+                    name,
+                    output.trim_end()
+                );
+            }
+        }
+    }
+
+    for child in &task.children {
+        markdown_task(text, details, child, depth + 1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{junit, ReportSink, Status};
+    use crate::events::{Event, EventRecord, EventSink, TaskOutput};
+
+    fn record(event: Event) -> EventRecord {
+        EventRecord {
+            timestamp_ms: 0,
+            event,
+        }
+    }
+
+    fn sink_with_run() -> ReportSink {
+        let mut sink = ReportSink::new(Vec::new());
+        let events = vec![
+            Event::JobStarted {
+                job: "deploy".to_string(),
+            },
+            Event::TaskStarted {
+                path: "0".to_string(),
+                task: "job build".to_string(),
+            },
+            Event::TaskStarted {
+                path: "0.0".to_string(),
+                task: "bash: make".to_string(),
+            },
+            Event::TaskFinished {
+                path: "0.0".to_string(),
+                task: "bash: make".to_string(),
+                duration_ms: 1500,
+                output: TaskOutput {
+                    exit_code: Some(0),
+                    stdout: Some("<ok>".to_string()),
+                    stderr: None,
+                },
+                cleanup: false,
+            },
+            Event::TaskFinished {
+                path: "0".to_string(),
+                task: "job build".to_string(),
+                duration_ms: 1500,
+                output: TaskOutput::default(),
+                cleanup: false,
+            },
+            Event::TaskSkipped {
+                path: "1".to_string(),
+                task: "on Windows".to_string(),
+                reason: "not running on Windows".to_string(),
+            },
+            Event::TaskStarted {
+                path: "2".to_string(),
+                task: "bash: false".to_string(),
+            },
+            Event::TaskFailed {
+                path: "2".to_string(),
+                task: "bash: false".to_string(),
+                duration_ms: 2,
+                output: TaskOutput {
+                    exit_code: Some(1),
+                    stdout: None,
+                    stderr: None,
+                },
+                error: vec!["command failed".to_string()],
+            },
+            Event::JobFinished {
+                job: "deploy".to_string(),
+                duration_ms: 1502,
+                success: false,
+            },
+        ];
+        for event in events {
+            sink.handle(&record(event)).unwrap();
+        }
+        sink.finish().unwrap();
+        sink
+    }
+
+    #[test]
+    fn build_tree_test() {
+        let sink = sink_with_run();
+        let tasks = &sink.jobs[0].tasks;
+        assert_eq!(tasks.len(), 3);
+        assert_eq!(tasks[0].children.len(), 1);
+        assert_eq!(tasks[0].children[0].status, Status::Passed);
+        assert_eq!(tasks[1].status, Status::Skipped);
+        assert_eq!(tasks[2].status, Status::Failed);
+        assert_eq!(sink.jobs[0].counts(), (3, 1, 1));
+    }
+
+    #[test]
+    fn junit_test() {
+        let sink = sink_with_run();
+        assert_eq!(
+            junit(&sink.jobs),
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<testsuites name="workflowo" tests="3" failures="1" skipped="1" time="1.502">
+  <testsuite name="deploy" tests="3" failures="1" skipped="1" time="1.502">
+    <testsuite name="0 job build" tests="1" failures="0" skipped="0" time="1.500">
+      <testcase name="0.0 bash: make" classname="deploy.0" time="1.500">
+        <system-out>&lt;ok&gt;</system-out>
+      </testcase>
+    </testsuite>
+    <testcase name="1 on Windows" classname="deploy" time="0.000">
+      <skipped message="not running on Windows"/>
+    </testcase>
+    <testcase name="2 bash: false" classname="deploy" time="0.002">
+      <failure message="command failed">command failed</failure>
+    </testcase>
+  </testsuite>
+</testsuites>
+"#
+        );
+    }
+}
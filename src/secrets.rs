@@ -0,0 +1,159 @@
+use crate::history::data_dir;
+use crate::mask;
+use age::secrecy::SecretString;
+use anyhow::{bail, Context, Result};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// The passphrase once it was entered, so that it is only prompted once per run.
+static PASSPHRASE: Mutex<Option<String>> = Mutex::new(None);
+
+/// The decrypted secrets of the default file, they are only decrypted once per run.
+static LOADED: Mutex<Option<BTreeMap<String, String>>> = Mutex::new(None);
+
+/// Returns the path of the secrets file. `WORKFLOWO_SECRETS_FILE` overrides the file in the data
+/// directory.
+pub fn secrets_file() -> Result<PathBuf> {
+    if let Some(file) = std::env::var_os("WORKFLOWO_SECRETS_FILE") {
+        return Ok(PathBuf::from(file));
+    }
+    Ok(data_dir()?.join("secrets.age"))
+}
+
+/// Returns the passphrase of the secrets file. It is taken from `WORKFLOWO_SECRETS_PASSPHRASE` or
+/// prompted for. A new passphrase has to be entered twice.
+pub fn passphrase(new: bool) -> Result<String> {
+    let mut cached = PASSPHRASE.lock().unwrap();
+    if let Some(passphrase) = cached.as_ref() {
+        return Ok(passphrase.clone());
+    }
+    let passphrase = match std::env::var("WORKFLOWO_SECRETS_PASSPHRASE") {
+        Ok(passphrase) => passphrase,
+        Err(_) if new => {
+            let passphrase = rpassword::prompt_password("New passphrase of the secrets: ")
+                .context("hidden input failed (rpassword)")?;
+            let repeated = rpassword::prompt_password("Repeat the passphrase: ")
+                .context("hidden input failed (rpassword)")?;
+            if passphrase != repeated {
+                bail!("The passphrases do not match");
+            }
+            passphrase
+        }
+        Err(_) => rpassword::prompt_password("Passphrase of the secrets: ")
+            .context("hidden input failed (rpassword)")?,
+    };
+    if passphrase.is_empty() {
+        bail!("The passphrase is empty");
+    }
+    *cached = Some(passphrase.clone());
+    Ok(passphrase)
+}
+
+/// A file with secrets that is encrypted with a passphrase. The secrets are stored as a yaml map
+/// from names to values.
+pub struct SecretsFile {
+    path: PathBuf,
+    /// the scrypt work factor, if not given it is chosen to take about a second
+    work_factor: Option<u8>,
+}
+
+impl SecretsFile {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            work_factor: None,
+        }
+    }
+
+    /// Opens the default secrets file.
+    pub fn open() -> Result<Self> {
+        Ok(Self::new(secrets_file()?))
+    }
+
+    pub fn exists(&self) -> bool {
+        self.path.exists()
+    }
+
+    /// Decrypts all secrets. Empty if the file does not exist.
+    pub fn load(&self, passphrase: &str) -> Result<BTreeMap<String, String>> {
+        if !self.exists() {
+            return Ok(BTreeMap::new());
+        }
+        let encrypted =
+            std::fs::read(&self.path).context(format!("Could not read {:?}", self.path))?;
+        let identity = age::scrypt::Identity::new(SecretString::from(passphrase.to_string()));
+        let decrypted = age::decrypt(&identity, &encrypted).context(format!(
+            "Could not decrypt {:?}, the passphrase may be wrong",
+            self.path
+        ))?;
+        let secrets: BTreeMap<String, String> = serde_yaml::from_slice(&decrypted)
+            .context(format!("Secrets {:?} are invalid", self.path))?;
+        for value in secrets.values() {
+            mask::register(value);
+        }
+        Ok(secrets)
+    }
+
+    /// Encrypts the secrets and replaces the file.
+    pub fn save(&self, passphrase: &str, secrets: &BTreeMap<String, String>) -> Result<()> {
+        let text = serde_yaml::to_string(secrets).context("Could not serialize the secrets")?;
+        let mut recipient = age::scrypt::Recipient::new(SecretString::from(passphrase.to_string()));
+        if let Some(work_factor) = self.work_factor {
+            recipient.set_work_factor(work_factor);
+        }
+        let encrypted =
+            age::encrypt(&recipient, text.as_bytes()).context("Could not encrypt the secrets")?;
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir).context(format!("Could not create {:?}", dir))?;
+        }
+        std::fs::write(&self.path, encrypted).context(format!("Could not write {:?}", self.path))
+    }
+}
+
+/// Returns the secret with the name from the default secrets file. The value is masked in all
+/// output.
+pub fn lookup(name: &str) -> Result<String> {
+    let mut loaded = LOADED.lock().unwrap();
+    if loaded.is_none() {
+        let file = SecretsFile::open()?;
+        if !file.exists() {
+            bail!(format!(
+                "There is no secrets file {:?}, add secrets with `workflowo secrets set`",
+                file.path
+            ));
+        }
+        *loaded = Some(file.load(&passphrase(false)?)?);
+    }
+    match loaded.as_ref().unwrap().get(name) {
+        Some(value) => Ok(value.clone()),
+        None => bail!(format!("Secret {} does not exist", name)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SecretsFile;
+    use crate::mask;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn secrets_file_test() {
+        let dir = std::env::temp_dir().join(format!("workflowo-secrets-{}", std::process::id()));
+        let mut file = SecretsFile::new(dir.join("secrets.age"));
+        file.work_factor = Some(2);
+        assert!(file.load("passphrase").unwrap().is_empty());
+
+        let mut secrets = BTreeMap::new();
+        secrets.insert("db".to_string(), "secrets-file-test-value".to_string());
+        file.save("passphrase", &secrets).unwrap();
+        let encrypted = std::fs::read(dir.join("secrets.age")).unwrap();
+        assert!(!String::from_utf8_lossy(&encrypted).contains("secrets-file-test-value"));
+
+        assert!(file.load("wrong").is_err());
+        assert_eq!(file.load("passphrase").unwrap(), secrets);
+        assert_eq!(mask::mask("secrets-file-test-value"), mask::MASK);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,45 @@
+use crate::cli::ExitCode;
+use crate::tasks::CancelToken;
+use anyhow::{Context, Result};
+use signal_hook::consts::TERM_SIGNALS;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Replaces the default handling of SIGINT and SIGTERM. The handler is called with the number of
+/// signals received so far.
+pub fn on_termination(handler: impl Fn(usize) + Send + 'static) -> Result<()> {
+    let received = Arc::new(AtomicBool::new(false));
+    for signal in TERM_SIGNALS {
+        signal_hook::flag::register(*signal, received.clone()).context(format!(
+            "Could not register a handler for signal {}",
+            signal
+        ))?;
+    }
+    std::thread::spawn(move || {
+        let mut count = 0;
+        loop {
+            std::thread::sleep(Duration::from_millis(50));
+            if received.swap(false, Ordering::SeqCst) {
+                count += 1;
+                handler(count);
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Cancels the run on the first signal, forces it on the second and exits on the third.
+pub fn cancel_run(count: usize, token: &CancelToken) {
+    match count {
+        1 => {
+            eprintln!("Cancelling the run, press Ctrl-C again to kill running processes");
+            token.cancel();
+        }
+        2 => {
+            eprintln!("Killing running processes");
+            token.force();
+        }
+        _ => ExitCode::Cancelled.exit(),
+    }
+}
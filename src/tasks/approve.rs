@@ -0,0 +1,223 @@
+use super::{RunContext, Task};
+use crate::events::Event;
+use anyhow::{bail, Result};
+use std::fmt;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// The lines of stdin. One thread reads stdin, so an approval that timed out does not take the
+/// answer of the next one.
+static STDIN_LINES: OnceLock<Mutex<Receiver<String>>> = OnceLock::new();
+
+fn stdin_lines() -> &'static Mutex<Receiver<String>> {
+    STDIN_LINES.get_or_init(|| {
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            for line in std::io::stdin().lines().map_while(Result::ok) {
+                if sender.send(line).is_err() {
+                    return;
+                }
+            }
+        });
+        Mutex::new(receiver)
+    })
+}
+
+/// What happens when nobody answers before the timeout.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OnTimeout {
+    Abort,
+    Approve,
+}
+
+/// Waits until a human approves the tasks that follow. The job is aborted otherwise.
+#[derive(Debug)]
+pub struct ApproveTask {
+    message: String,
+    timeout: Option<Duration>,
+    on_timeout: OnTimeout,
+    /// the descriptions of the tasks that follow in the job
+    summary: Vec<String>,
+}
+
+impl ApproveTask {
+    pub fn new(message: String, timeout: Option<Duration>, on_timeout: OnTimeout) -> Self {
+        Self {
+            message,
+            timeout,
+            on_timeout,
+            summary: Vec::new(),
+        }
+    }
+
+    pub fn set_summary(&mut self, summary: Vec<String>) {
+        self.summary = summary;
+    }
+
+    /// Waits for the answer in the lines. Returns `None` if the timeout passed.
+    fn wait_for_answer(
+        &self,
+        context: &RunContext,
+        lines: &Mutex<Receiver<String>>,
+    ) -> Result<Option<String>> {
+        let start = Instant::now();
+        let lines = lines.lock().unwrap();
+        loop {
+            if context.cancel.is_cancelled() {
+                bail!("the run was cancelled");
+            }
+            let wait = match self.timeout {
+                Some(timeout) => match timeout.checked_sub(start.elapsed()) {
+                    Some(left) => left.min(Duration::from_millis(100)),
+                    None => return Ok(None),
+                },
+                None => Duration::from_millis(100),
+            };
+            match lines.recv_timeout(wait) {
+                Ok(line) => return Ok(Some(line)),
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => {
+                    bail!("stdin was closed before the approval, use --yes to approve")
+                }
+            }
+        }
+    }
+}
+
+/// Returns the name of the user that runs workflowo.
+fn user() -> String {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_else(|_| "unknown user".to_string())
+}
+
+impl ApproveTask {
+    /// Asks for the approval and reads the answer from the lines.
+    fn approve(&self, context: &mut RunContext, lines: &Mutex<Receiver<String>>) -> Result<()> {
+        context.message(&self.message);
+        if !self.summary.is_empty() {
+            context.message("The following tasks will be executed:");
+            for task in &self.summary {
+                context.message(&format!("    {}", task));
+            }
+        }
+
+        let approved_by = if context.approve {
+            context.message("Approved with --yes");
+            format!("{} (--yes)", user())
+        } else {
+            match self.timeout {
+                Some(timeout) => context.message(&format!(
+                    "Type yes to continue ({}s left):",
+                    timeout.as_secs()
+                )),
+                None => context.message("Type yes to continue:"),
+            }
+            match self.wait_for_answer(context, lines)? {
+                Some(answer) if answer.trim().eq_ignore_ascii_case("yes") => user(),
+                Some(answer) => bail!(format!("Not approved (answer {:?})", answer.trim())),
+                None if self.on_timeout == OnTimeout::Approve => {
+                    context.message("No answer, approved because of the timeout");
+                    "timeout".to_string()
+                }
+                None => bail!("Not approved before the timeout"),
+            }
+        };
+
+        context.events.emit(Event::TaskApproved {
+            path: context.path(),
+            task: self.describe(),
+            approved_by,
+        });
+        Ok(())
+    }
+}
+
+impl Task for ApproveTask {
+    fn execute(&self, context: &mut RunContext) -> Result<()> {
+        self.approve(context, stdin_lines())
+    }
+
+    fn describe(&self) -> String {
+        format!("approve \"{}\"", self.message)
+    }
+}
+
+impl fmt::Display for ApproveTask {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{user, ApproveTask, OnTimeout};
+    use crate::events::{Event, EventRecord, EventSink};
+    use crate::tasks::{RunContext, Task};
+    use anyhow::Result;
+    use std::sync::mpsc;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    struct ApprovedBy(Arc<Mutex<Vec<String>>>);
+
+    impl EventSink for ApprovedBy {
+        fn handle(&mut self, record: &EventRecord) -> Result<()> {
+            if let Event::TaskApproved { approved_by, .. } = &record.event {
+                self.0.lock().unwrap().push(approved_by.clone());
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn approve_test() {
+        let approved_by = Arc::new(Mutex::new(Vec::new()));
+        let mut context = RunContext::default();
+        context
+            .events
+            .add_sink(Box::new(ApprovedBy(approved_by.clone())));
+        let task = ApproveTask::new("Deploy?".to_string(), None, OnTimeout::Abort);
+        let answer = |context: &mut RunContext, lines: &[&str]| {
+            let (sender, receiver) = mpsc::channel();
+            for line in lines {
+                sender.send(line.to_string()).unwrap();
+            }
+            drop(sender);
+            task.approve(context, &Mutex::new(receiver))
+        };
+
+        answer(&mut context, &[" YES "]).unwrap();
+        let error = answer(&mut context, &["no", "yes"]).unwrap_err();
+        assert!(error.to_string().contains("Not approved"));
+        let error = answer(&mut context, &[]).unwrap_err();
+        assert!(error.to_string().contains("--yes"));
+
+        // --yes and WORKFLOWO_YES approve without reading stdin
+        context.approve = true;
+        answer(&mut context, &[]).unwrap();
+        task.execute(&mut context).unwrap();
+
+        context.approve = false;
+        let timeout = ApproveTask::new(
+            "Deploy?".to_string(),
+            Some(Duration::ZERO),
+            OnTimeout::Approve,
+        );
+        let (_sender, receiver) = mpsc::channel();
+        timeout
+            .approve(&mut context, &Mutex::new(receiver))
+            .unwrap();
+
+        assert_eq!(
+            *approved_by.lock().unwrap(),
+            [
+                user(),
+                format!("{} (--yes)", user()),
+                format!("{} (--yes)", user()),
+                "timeout".to_string()
+            ]
+        );
+    }
+}
@@ -0,0 +1,717 @@
+use super::ssh::{connect_ssh, execute_on_session, upload_sftp_file};
+use super::template::Destination;
+use super::{RunContext, Task};
+use crate::events::TaskOutput;
+use crate::mask;
+use anyhow::{bail, Context, Result};
+use std::fmt::{self, Display};
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::{Component, Path, PathBuf};
+
+/// The format of an archive.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Tar,
+    TarGz,
+    TarZst,
+    Zip,
+}
+
+impl Format {
+    /// Parses `tar`, `tar.gz`, `tgz`, `tar.zst`, `tzst` or `zip`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "tar" => Some(Self::Tar),
+            "tar.gz" | "tgz" => Some(Self::TarGz),
+            "tar.zst" | "tzst" => Some(Self::TarZst),
+            "zip" => Some(Self::Zip),
+            _ => None,
+        }
+    }
+
+    /// Detects the format by the extension of the archive.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_string_lossy().to_lowercase();
+        ["tar.gz", "tar.zst", "tgz", "tzst", "tar", "zip"]
+            .into_iter()
+            .find(|extension| name.ends_with(&format!(".{}", extension)))
+            .and_then(Self::from_name)
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Tar => "tar",
+            Self::TarGz => "tar.gz",
+            Self::TarZst => "tar.zst",
+            Self::Zip => "zip",
+        }
+    }
+}
+
+/// Decides which files of a directory are put into an archive.
+#[derive(Debug, Default)]
+pub struct Filter {
+    /// globs of the files that are included, all files if empty
+    include: Vec<glob::Pattern>,
+    /// globs of the files and directories that are left out
+    exclude: Vec<glob::Pattern>,
+}
+
+impl Filter {
+    /// The globs are matched against the paths relative to the archived directory.
+    pub fn new(include: &[String], exclude: &[String]) -> Result<Self> {
+        let compile = |patterns: &[String]| {
+            patterns
+                .iter()
+                .map(|pattern| {
+                    glob::Pattern::new(pattern).context(format!("invalid glob {}", pattern))
+                })
+                .collect::<Result<Vec<_>>>()
+        };
+        Ok(Self {
+            include: compile(include)?,
+            exclude: compile(exclude)?,
+        })
+    }
+
+    fn is_excluded(&self, path: &Path) -> bool {
+        self.exclude
+            .iter()
+            .any(|pattern| pattern.matches_path(path))
+    }
+
+    fn is_included(&self, path: &Path) -> bool {
+        self.include.is_empty()
+            || self
+                .include
+                .iter()
+                .any(|pattern| pattern.matches_path(path))
+    }
+}
+
+/// Collects the paths relative to `root` that are archived. Directories are only archived
+/// without include globs, otherwise they are created for their files.
+fn collect(
+    root: &Path,
+    dir: &Path,
+    filter: &Filter,
+    skip: &Path,
+    entries: &mut Vec<PathBuf>,
+) -> Result<()> {
+    let mut children = Vec::new();
+    for entry in std::fs::read_dir(dir).context(format!("Could not read {:?}", dir))? {
+        children.push(entry.context(format!("Could not read {:?}", dir))?.path());
+    }
+    children.sort();
+    for path in children {
+        let relative = path.strip_prefix(root).unwrap().to_path_buf();
+        if path == skip || filter.is_excluded(&relative) {
+            continue;
+        }
+        let metadata = path
+            .symlink_metadata()
+            .context(format!("Could not read {:?}", path))?;
+        if metadata.is_dir() {
+            if filter.include.is_empty() {
+                entries.push(relative);
+            }
+            collect(root, &path, filter, skip, entries)?;
+        } else if filter.is_included(&relative) {
+            entries.push(relative);
+        }
+    }
+    Ok(())
+}
+
+/// Returns true if the relative path leaves the directory it is relative to.
+fn escapes(path: &Path) -> bool {
+    let mut depth = 0;
+    for component in path.components() {
+        match component {
+            Component::Prefix(_) | Component::RootDir => return true,
+            Component::ParentDir if depth == 0 => return true,
+            Component::ParentDir => depth -= 1,
+            Component::Normal(_) => depth += 1,
+            Component::CurDir => {}
+        }
+    }
+    false
+}
+
+/// Refuses entries that would be written outside of the destination. Symlinks may only point
+/// to paths inside of it.
+fn check_entry(path: &Path, symlink: Option<&Path>) -> Result<()> {
+    if escapes(path) {
+        bail!(format!(
+            "Refusing to extract {:?}, it leaves the destination",
+            path
+        ));
+    }
+    if let Some(target) = symlink {
+        if escapes(&path.parent().unwrap_or(Path::new("")).join(target)) {
+            bail!(format!(
+                "Refusing to extract the symlink {:?} -> {:?}, it points out of the destination",
+                path, target
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Refuses entries that would be written outside of `root` because a symlink extracted before
+/// them is part of their path. Symlinks may only point to paths inside of `root` after the
+/// symlinks in their own path are resolved.
+fn check_resolved(root: &Path, path: &Path, symlink: Option<&Path>) -> Result<()> {
+    let root = root
+        .canonicalize()
+        .context(format!("Could not resolve {:?}", root))?;
+    // the parents that do not exist yet are created inside of the last one that exists
+    let mut existing = root.join(path);
+    existing.pop();
+    let mut missing = PathBuf::new();
+    while existing.symlink_metadata().is_err() {
+        if let Some(name) = existing.file_name() {
+            missing = Path::new(name).join(missing);
+        }
+        existing.pop();
+    }
+    let resolved = existing
+        .canonicalize()
+        .context(format!("Could not resolve {:?}", existing))?;
+    let inside = match resolved.strip_prefix(&root) {
+        Ok(inside) => inside.join(missing),
+        Err(_) => bail!(format!(
+            "Refusing to extract {:?}, a symlink in its path leaves the destination",
+            path
+        )),
+    };
+    if let Some(target) = symlink {
+        if escapes(&inside.join(target)) {
+            bail!(format!(
+                "Refusing to extract the symlink {:?} -> {:?}, it points out of the destination",
+                path, target
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn write_tar<W: Write>(writer: W, root: &Path, entries: &[PathBuf]) -> Result<W> {
+    let mut builder = tar::Builder::new(writer);
+    builder.follow_symlinks(false);
+    for entry in entries {
+        builder
+            .append_path_with_name(root.join(entry), entry)
+            .context(format!("Could not archive {:?}", root.join(entry)))?;
+    }
+    builder
+        .into_inner()
+        .context("Could not finish the tar archive")
+}
+
+fn write_zip(file: File, root: &Path, entries: &[PathBuf]) -> Result<()> {
+    let mut zip = zip::ZipWriter::new(file);
+    for entry in entries {
+        let path = root.join(entry);
+        let name = entry.to_string_lossy().replace('\\', "/");
+        let metadata = path
+            .symlink_metadata()
+            .context(format!("Could not read {:?}", path))?;
+        let options = zip::write::SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Deflated)
+            .large_file(metadata.len() >= u32::MAX as u64);
+        #[cfg(unix)]
+        let options = {
+            use std::os::unix::fs::PermissionsExt;
+            options.unix_permissions(metadata.permissions().mode())
+        };
+        if metadata.is_dir() {
+            zip.add_directory(name, options)
+                .context(format!("Could not archive {:?}", path))?;
+        } else if metadata.is_symlink() {
+            let target = std::fs::read_link(&path).context(format!("Could not read {:?}", path))?;
+            zip.add_symlink(name, target.to_string_lossy(), options)
+                .context(format!("Could not archive {:?}", path))?;
+        } else {
+            zip.start_file(name, options)
+                .context(format!("Could not archive {:?}", path))?;
+            let mut source = File::open(&path).context(format!("Could not open {:?}", path))?;
+            std::io::copy(&mut source, &mut zip)
+                .context(format!("Could not archive {:?}", path))?;
+        }
+    }
+    zip.finish().context("Could not finish the zip archive")?;
+    Ok(())
+}
+
+/// Checks the entries of a tar archive and extracts them into `dest` if it is given.
+fn read_tar<R: Read>(reader: R, dest: Option<&Path>) -> Result<()> {
+    let mut archive = tar::Archive::new(reader);
+    archive.set_preserve_permissions(true);
+    archive.set_overwrite(true);
+    for entry in archive
+        .entries()
+        .context("Could not read the tar archive")?
+    {
+        let mut entry = entry.context("Could not read an entry of the tar archive")?;
+        let path = entry
+            .path()
+            .context("An entry of the tar archive has an invalid path")?
+            .into_owned();
+        let entry_type = entry.header().entry_type();
+        let link = entry
+            .link_name()
+            .context(format!("Could not read the link of {:?}", path))?
+            .map(|link| link.into_owned());
+        match (entry_type.is_symlink(), entry_type.is_hard_link(), &link) {
+            (true, _, Some(link)) => check_entry(&path, Some(link))?,
+            // hard links are relative to the root of the archive
+            (_, true, Some(link)) => {
+                check_entry(&path, None)?;
+                check_entry(link, None)?;
+            }
+            _ => check_entry(&path, None)?,
+        }
+        if let Some(dest) = dest {
+            entry
+                .unpack_in(dest)
+                .context(format!("Could not extract {:?}", path))?;
+        }
+    }
+    Ok(())
+}
+
+/// Checks the entries of a zip archive and extracts them into `dest` if it is given.
+fn read_zip(file: File, dest: Option<&Path>) -> Result<()> {
+    let mut archive = zip::ZipArchive::new(file).context("Could not read the zip archive")?;
+    for index in 0..archive.len() {
+        let mut entry = archive
+            .by_index(index)
+            .context("Could not read an entry of the zip archive")?;
+        let path = match entry.enclosed_name() {
+            Some(path) => path,
+            None => bail!(format!(
+                "Refusing to extract {:?}, it leaves the destination",
+                entry.name()
+            )),
+        };
+        let symlink = match entry.is_symlink() {
+            true => {
+                let mut target = String::new();
+                entry
+                    .read_to_string(&mut target)
+                    .context(format!("Could not read the link of {:?}", path))?;
+                Some(PathBuf::from(target))
+            }
+            false => None,
+        };
+        check_entry(&path, symlink.as_deref())?;
+        let dest = match dest {
+            Some(root) => {
+                check_resolved(root, &path, symlink.as_deref())?;
+                root.join(&path)
+            }
+            None => continue,
+        };
+
+        if entry.is_dir() {
+            std::fs::create_dir_all(&dest).context(format!("Could not create {:?}", dest))?;
+        } else {
+            if let Some(parent) = dest.parent() {
+                std::fs::create_dir_all(parent)
+                    .context(format!("Could not create {:?}", parent))?;
+            }
+            if dest.symlink_metadata().is_ok() && !dest.is_dir() {
+                std::fs::remove_file(&dest).context(format!("Could not replace {:?}", dest))?;
+            }
+            match &symlink {
+                #[cfg(unix)]
+                Some(target) => std::os::unix::fs::symlink(target, &dest)
+                    .context(format!("Could not create the symlink {:?}", dest))?,
+                #[cfg(not(unix))]
+                Some(target) => std::fs::write(&dest, target.to_string_lossy().as_bytes())
+                    .context(format!("Could not write {:?}", dest))?,
+                None => {
+                    let mut file =
+                        File::create(&dest).context(format!("Could not create {:?}", dest))?;
+                    std::io::copy(&mut entry, &mut file)
+                        .context(format!("Could not extract {:?}", dest))?;
+                }
+            }
+        }
+        #[cfg(unix)]
+        if let (Some(mode), None) = (entry.unix_mode(), &symlink) {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&dest, std::fs::Permissions::from_mode(mode & 0o7777))
+                .context(format!("Could not set the mode of {:?}", dest))?;
+        }
+    }
+    Ok(())
+}
+
+/// Checks all entries of the archive and extracts them into `dest` if it is given.
+fn read_archive(path: &Path, format: Format, dest: Option<&Path>) -> Result<()> {
+    let file = File::open(path).context(format!("Could not open {:?}", path))?;
+    match format {
+        Format::Tar => read_tar(file, dest),
+        Format::TarGz => read_tar(flate2::read::GzDecoder::new(file), dest),
+        Format::TarZst => read_tar(
+            zstd::Decoder::new(file).context("Could not read the zstd stream")?,
+            dest,
+        ),
+        Format::Zip => read_zip(file, dest),
+    }
+}
+
+/// Puts a directory or file into an archive.
+#[derive(Debug)]
+pub struct ArchiveTask {
+    src: PathBuf,
+    dest: PathBuf,
+    format: Format,
+    filter: Filter,
+}
+
+impl ArchiveTask {
+    pub fn new(src: PathBuf, dest: PathBuf, format: Format, filter: Filter) -> Self {
+        Self {
+            src,
+            dest,
+            format,
+            filter,
+        }
+    }
+}
+
+impl Task for ArchiveTask {
+    fn execute(&self, _context: &mut RunContext) -> Result<()> {
+        // the content of a directory is at the root of the archive
+        let (root, entries) = if self.src.is_dir() {
+            let mut entries = Vec::new();
+            collect(&self.src, &self.src, &self.filter, &self.dest, &mut entries)?;
+            (self.src.clone(), entries)
+        } else if self.src.exists() {
+            let name = self
+                .src
+                .file_name()
+                .context(format!("{:?} has no file name", self.src))?;
+            (
+                self.src.parent().unwrap_or(Path::new("")).to_path_buf(),
+                vec![PathBuf::from(name)],
+            )
+        } else {
+            bail!(format!("{:?} does not exist", self.src));
+        };
+
+        let file = File::create(&self.dest).context(format!("Could not create {:?}", self.dest))?;
+        match self.format {
+            Format::Tar => write_tar(file, &root, &entries).map(|_| ()),
+            Format::TarGz => write_tar(
+                flate2::write::GzEncoder::new(file, flate2::Compression::default()),
+                &root,
+                &entries,
+            )?
+            .finish()
+            .map(|_| ())
+            .context("Could not finish the gzip stream"),
+            Format::TarZst => write_tar(
+                zstd::Encoder::new(file, 0).context("Could not create the zstd stream")?,
+                &root,
+                &entries,
+            )?
+            .finish()
+            .map(|_| ())
+            .context("Could not finish the zstd stream"),
+            Format::Zip => write_zip(file, &root, &entries),
+        }
+    }
+
+    fn describe(&self) -> String {
+        format!(
+            "archive {} -> {} ({})",
+            self.src.display(),
+            self.dest.display(),
+            self.format.name()
+        )
+    }
+}
+
+impl Display for ArchiveTask {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+/// Quotes a text for a posix shell.
+fn quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "'\\''"))
+}
+
+/// Extracts an archive into a directory. A remote archive is uploaded and extracted with `tar`
+/// or `unzip` on the remote computer.
+#[derive(Debug)]
+pub struct ExtractTask {
+    src: PathBuf,
+    dest: Destination,
+    format: Format,
+}
+
+impl ExtractTask {
+    pub fn new(src: PathBuf, dest: Destination, format: Format) -> Self {
+        if let Destination::Remote { password, .. } = &dest {
+            mask::register(password);
+        }
+        Self { src, dest, format }
+    }
+}
+
+impl Task for ExtractTask {
+    fn execute(&self, context: &mut RunContext) -> Result<()> {
+        let (address, user, password, path) = match &self.dest {
+            Destination::Local(dest) => {
+                std::fs::create_dir_all(dest).context(format!("Could not create {:?}", dest))?;
+                return read_archive(&self.src, self.format, Some(dest));
+            }
+            Destination::Remote {
+                address,
+                user,
+                password,
+                path,
+            } => (address, user, password, path),
+        };
+        // the remote tools are not trusted to refuse path traversal
+        read_archive(&self.src, self.format, None)?;
+
+        let session = connect_ssh(&address.to_string(), user, password)
+            .context("Error while connect via ssh")?;
+        let sftp = session.sftp().context("Could not create sftp subsystem")?;
+        // a fresh directory, so other users of the remote computer can not replace the archive
+        let mut temp_dir = String::new();
+        let exit_code =
+            execute_on_session(&session, "mktemp -d", &mut temp_dir, false, &context.cancel)
+                .context("Could not create a temporary directory via ssh")?;
+        if exit_code != 0 {
+            bail!(format!(
+                "Creating a temporary directory failed with exit code {}: {}",
+                exit_code,
+                temp_dir.trim_end()
+            ));
+        }
+        let upload = Path::new(temp_dir.trim_end()).join("archive");
+        let temp_dir = quote(temp_dir.trim_end());
+        if let Err(error) = upload_sftp_file(&sftp, &self.src, &upload) {
+            let _ = execute_on_session(
+                &session,
+                &format!("rm -rf {}", temp_dir),
+                &mut String::new(),
+                false,
+                &context.cancel,
+            );
+            return Err(error.context("Could not upload the archive"));
+        }
+
+        let archive = quote(&upload.to_string_lossy());
+        let dest = quote(&path.to_string_lossy());
+        let extract = match self.format {
+            Format::Tar => format!("tar -xpf {} -C {}", archive, dest),
+            Format::TarGz => format!("tar -xzpf {} -C {}", archive, dest),
+            Format::TarZst => format!("tar --zstd -xpf {} -C {}", archive, dest),
+            Format::Zip => format!("unzip -o -q {} -d {}", archive, dest),
+        };
+        let command = format!(
+            "mkdir -p {dest} && {extract} 2>&1; status=$?; rm -rf {temp_dir}; exit $status"
+        );
+        let mut output = String::new();
+        let exit_code = execute_on_session(&session, &command, &mut output, false, &context.cancel)
+            .context("Could not extract the archive via ssh")?;
+        context.record_output(TaskOutput {
+            exit_code: Some(exit_code),
+            stdout: Some(output.clone()),
+            stderr: None,
+        });
+        if exit_code != 0 {
+            bail!(format!(
+                "Extracting failed with exit code {}: {}",
+                exit_code,
+                output.trim_end()
+            ));
+        }
+        Ok(())
+    }
+
+    fn describe(&self) -> String {
+        let dest = match &self.dest {
+            Destination::Local(path) => path.display().to_string(),
+            Destination::Remote {
+                address,
+                user,
+                path,
+                ..
+            } => format!("{}@{}:{}", user, address, path.display()),
+        };
+        format!(
+            "extract {} -> {} ({})",
+            self.src.display(),
+            dest,
+            self.format.name()
+        )
+    }
+}
+
+impl Display for ExtractTask {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", mask::mask(&format!("{:?}", self)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ArchiveTask, Destination, ExtractTask, Filter, Format};
+    use crate::tasks::{RunContext, Task};
+    use std::io::Write;
+    use std::path::Path;
+
+    #[test]
+    fn format_test() {
+        assert_eq!(
+            Format::from_path(Path::new("a.tar.gz")),
+            Some(Format::TarGz)
+        );
+        assert_eq!(Format::from_path(Path::new("a.TGZ")), Some(Format::TarGz));
+        assert_eq!(
+            Format::from_path(Path::new("a.tar.zst")),
+            Some(Format::TarZst)
+        );
+        assert_eq!(Format::from_path(Path::new("a.tar")), Some(Format::Tar));
+        assert_eq!(Format::from_path(Path::new("a.zip")), Some(Format::Zip));
+        assert_eq!(Format::from_path(Path::new("a.gz")), None);
+    }
+
+    #[test]
+    fn archive_extract_test() {
+        let dir = std::env::temp_dir().join(format!("workflowo-archive-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("site/css")).unwrap();
+        std::fs::create_dir_all(dir.join("site/.git")).unwrap();
+        std::fs::write(dir.join("site/index.html"), "index").unwrap();
+        std::fs::write(dir.join("site/css/main.css"), "css").unwrap();
+        std::fs::write(dir.join("site/css/main.css.map"), "map").unwrap();
+        std::fs::write(dir.join("site/.git/HEAD"), "head").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::write(dir.join("site/run.sh"), "run").unwrap();
+            std::fs::set_permissions(
+                dir.join("site/run.sh"),
+                std::fs::Permissions::from_mode(0o750),
+            )
+            .unwrap();
+        }
+
+        for format in [Format::Tar, Format::TarGz, Format::TarZst, Format::Zip] {
+            let archive = dir.join(format!("site.{}", format.name()));
+            let out = dir.join(format!("out-{}", format.name()));
+            let filter = Filter::new(&[], &[".git".to_string(), "**/*.map".to_string()]).unwrap();
+            ArchiveTask::new(dir.join("site"), archive.clone(), format, filter)
+                .execute(&mut RunContext::default())
+                .unwrap();
+            ExtractTask::new(archive, Destination::Local(out.clone()), format)
+                .execute(&mut RunContext::default())
+                .unwrap();
+
+            assert_eq!(
+                std::fs::read_to_string(out.join("css/main.css")).unwrap(),
+                "css"
+            );
+            assert!(out.join("index.html").exists());
+            assert!(!out.join("css/main.css.map").exists());
+            assert!(!out.join(".git").exists());
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                let metadata = std::fs::metadata(out.join("run.sh")).unwrap();
+                assert_eq!(metadata.permissions().mode() & 0o777, 0o750, "{:?}", format);
+            }
+        }
+
+        // only the included files
+        let filter = Filter::new(&["**/*.css".to_string()], &[]).unwrap();
+        ArchiveTask::new(dir.join("site"), dir.join("css.zip"), Format::Zip, filter)
+            .execute(&mut RunContext::default())
+            .unwrap();
+        let mut names: Vec<String> =
+            zip::ZipArchive::new(std::fs::File::open(dir.join("css.zip")).unwrap())
+                .unwrap()
+                .file_names()
+                .map(|name| name.to_string())
+                .collect();
+        names.sort();
+        assert_eq!(names, ["css/main.css"]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn path_traversal_test() {
+        let dir = std::env::temp_dir().join(format!("workflowo-traversal-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        // the tar crate refuses to write such paths, so the header is written by hand
+        let mut builder = tar::Builder::new(Vec::new());
+        let mut header = tar::Header::new_gnu();
+        header.as_gnu_mut().unwrap().name[..13].copy_from_slice(b"../escape.txt");
+        header.set_size(4);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append(&header, &b"evil"[..]).unwrap();
+        std::fs::write(dir.join("evil.tar"), builder.into_inner().unwrap()).unwrap();
+        let error = ExtractTask::new(
+            dir.join("evil.tar"),
+            Destination::Local(dir.join("out")),
+            Format::Tar,
+        )
+        .execute(&mut RunContext::default())
+        .unwrap_err();
+        assert!(format!("{:#}", error).contains("Refusing"), "{:#}", error);
+        assert!(!dir.join("escape.txt").exists());
+
+        let mut zip = zip::ZipWriter::new(std::fs::File::create(dir.join("evil.zip")).unwrap());
+        zip.add_symlink(
+            "link",
+            "../../etc",
+            zip::write::SimpleFileOptions::default(),
+        )
+        .unwrap();
+        zip.finish().unwrap();
+        let error = ExtractTask::new(
+            dir.join("evil.zip"),
+            Destination::Local(dir.join("out")),
+            Format::Zip,
+        )
+        .execute(&mut RunContext::default())
+        .unwrap_err();
+        assert!(format!("{:#}", error).contains("Refusing"), "{:#}", error);
+
+        // every symlink points inside on its own, but the chain leaves the destination
+        let mut zip = zip::ZipWriter::new(std::fs::File::create(dir.join("chain.zip")).unwrap());
+        let options = zip::write::SimpleFileOptions::default();
+        zip.add_directory("x/", options).unwrap();
+        zip.add_symlink("x/y", "..", options).unwrap();
+        zip.add_symlink("x/y/z", "..", options).unwrap();
+        zip.start_file("x/y/z/pwn.txt", options).unwrap();
+        zip.write_all(b"evil").unwrap();
+        zip.finish().unwrap();
+        std::fs::create_dir_all(dir.join("chain")).unwrap();
+        let error = ExtractTask::new(
+            dir.join("chain.zip"),
+            Destination::Local(dir.join("chain").join("out")),
+            Format::Zip,
+        )
+        .execute(&mut RunContext::default())
+        .unwrap_err();
+        assert!(format!("{:#}", error).contains("Refusing"), "{:#}", error);
+        assert!(!dir.join("chain").join("pwn.txt").exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,614 @@
+use super::{RunContext, Task};
+use crate::mask;
+use anyhow::{bail, Context, Result};
+use std::fmt::{self, Display};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// What happens when the destination of a task already exists.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Overwrite {
+    /// the destination is replaced
+    Always,
+    /// the source is skipped
+    Never,
+    /// the task fails
+    Error,
+}
+
+/// Returns true if the path contains glob characters.
+fn is_glob(path: &Path) -> bool {
+    path.to_string_lossy().contains(['*', '?', '['])
+}
+
+/// Returns the paths that match the glob or the path itself if it is no glob.
+fn expand(pattern: &Path) -> Result<Vec<PathBuf>> {
+    if !is_glob(pattern) {
+        return Ok(vec![pattern.to_path_buf()]);
+    }
+    let pattern = pattern.to_string_lossy();
+    let mut paths = Vec::new();
+    for path in glob::glob(&pattern).context(format!("invalid glob {}", pattern))? {
+        paths.push(path.context(format!("could not read path of glob {}", pattern))?);
+    }
+    Ok(paths)
+}
+
+/// Returns true if something exists at the path, also a broken symlink.
+fn exists(path: &Path) -> bool {
+    path.symlink_metadata().is_ok()
+}
+
+/// Removes a file, symlink or directory with its content.
+fn remove_path(path: &Path) -> Result<()> {
+    let metadata = path
+        .symlink_metadata()
+        .context(format!("Could not read {:?}", path))?;
+    if metadata.is_dir() {
+        std::fs::remove_dir_all(path).context(format!("Could not remove {:?}", path))
+    } else {
+        std::fs::remove_file(path).context(format!("Could not remove {:?}", path))
+    }
+}
+
+/// Applies the overwrite policy. Returns false if the destination exists and is kept.
+fn prepare_destination(dest: &Path, overwrite: Overwrite) -> Result<bool> {
+    if !exists(dest) {
+        return Ok(true);
+    }
+    match overwrite {
+        Overwrite::Always => {
+            remove_path(dest)?;
+            Ok(true)
+        }
+        Overwrite::Never => Ok(false),
+        Overwrite::Error => bail!(format!("{:?} already exists", dest)),
+    }
+}
+
+/// Sets the permissions of the path. Outside of unix only the readonly flag is set.
+fn set_mode(path: &Path, mode: u32) -> Result<()> {
+    #[cfg(unix)]
+    let permissions = {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::Permissions::from_mode(mode)
+    };
+    #[cfg(not(unix))]
+    let permissions = {
+        let mut permissions = std::fs::metadata(path)
+            .context(format!("Could not read {:?}", path))?
+            .permissions();
+        permissions.set_readonly(mode & 0o222 == 0);
+        permissions
+    };
+    std::fs::set_permissions(path, permissions)
+        .context(format!("Could not set the mode of {:?}", path))
+}
+
+/// Copies a file or a directory with its content.
+fn copy_recursive(src: &Path, dest: &Path) -> Result<()> {
+    if src.is_dir() {
+        std::fs::create_dir(dest).context(format!("Could not create {:?}", dest))?;
+        for entry in std::fs::read_dir(src).context(format!("Could not read {:?}", src))? {
+            let entry = entry.context(format!("Could not read {:?}", src))?;
+            copy_recursive(&entry.path(), &dest.join(entry.file_name()))?;
+        }
+        Ok(())
+    } else {
+        std::fs::copy(src, dest)
+            .map(|_| ())
+            .context(format!("Could not copy {:?} to {:?}", src, dest))
+    }
+}
+
+/// Returns the sources with their destinations. The sources are put into `dest` if it is a
+/// directory or the source is a glob, otherwise `dest` is the new path of the source.
+fn transfers(src: &Path, dest: &Path) -> Result<Vec<(PathBuf, PathBuf)>> {
+    let sources = expand(src)?;
+    if sources.is_empty() {
+        bail!(format!("{:?} does not match any file", src));
+    }
+    let into_dir = is_glob(src) || dest.is_dir();
+    if into_dir && !dest.is_dir() {
+        bail!(format!("{:?} is not a directory", dest));
+    }
+    let mut transfers = Vec::new();
+    for source in sources {
+        if !exists(&source) {
+            bail!(format!("{:?} does not exist", source));
+        }
+        let target = match into_dir {
+            true => dest.join(
+                source
+                    .file_name()
+                    .context(format!("{:?} has no file name", source))?,
+            ),
+            false => dest.to_path_buf(),
+        };
+        if target.starts_with(&source) {
+            bail!(format!("{:?} is inside of {:?}", target, source));
+        }
+        transfers.push((source, target));
+    }
+    Ok(transfers)
+}
+
+/// Copies files or directories. The source can be a glob.
+#[derive(Debug)]
+pub struct CopyTask {
+    src: PathBuf,
+    dest: PathBuf,
+    overwrite: Overwrite,
+}
+
+impl CopyTask {
+    pub fn new(src: PathBuf, dest: PathBuf, overwrite: Overwrite) -> Self {
+        Self {
+            src,
+            dest,
+            overwrite,
+        }
+    }
+}
+
+impl Task for CopyTask {
+    fn execute(&self, context: &mut RunContext) -> Result<()> {
+        for (src, dest) in transfers(&self.src, &self.dest)? {
+            if !prepare_destination(&dest, self.overwrite)? {
+                context.message(&format!("{:?} exists, {:?} is not copied", dest, src));
+                continue;
+            }
+            copy_recursive(&src, &dest)?;
+        }
+        Ok(())
+    }
+
+    fn describe(&self) -> String {
+        format!("copy {} -> {}", self.src.display(), self.dest.display())
+    }
+}
+
+impl Display for CopyTask {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+/// Moves files or directories. The source can be a glob.
+#[derive(Debug)]
+pub struct MoveTask {
+    src: PathBuf,
+    dest: PathBuf,
+    overwrite: Overwrite,
+}
+
+impl MoveTask {
+    pub fn new(src: PathBuf, dest: PathBuf, overwrite: Overwrite) -> Self {
+        Self {
+            src,
+            dest,
+            overwrite,
+        }
+    }
+}
+
+impl Task for MoveTask {
+    fn execute(&self, context: &mut RunContext) -> Result<()> {
+        for (src, dest) in transfers(&self.src, &self.dest)? {
+            if !prepare_destination(&dest, self.overwrite)? {
+                context.message(&format!("{:?} exists, {:?} is not moved", dest, src));
+                continue;
+            }
+            // renaming fails between file systems
+            if std::fs::rename(&src, &dest).is_err() {
+                copy_recursive(&src, &dest)?;
+                remove_path(&src)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn describe(&self) -> String {
+        format!("move {} -> {}", self.src.display(), self.dest.display())
+    }
+}
+
+impl Display for MoveTask {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+/// Removes files and directories with their content. Paths that do not exist are ignored.
+#[derive(Debug)]
+pub struct RemoveTask {
+    path: PathBuf,
+}
+
+impl RemoveTask {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+}
+
+impl Task for RemoveTask {
+    fn execute(&self, _context: &mut RunContext) -> Result<()> {
+        for path in expand(&self.path)? {
+            if exists(&path) {
+                remove_path(&path)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn describe(&self) -> String {
+        format!("remove {}", self.path.display())
+    }
+}
+
+impl Display for RemoveTask {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+/// Creates a directory and its parents.
+#[derive(Debug)]
+pub struct MkdirTask {
+    path: PathBuf,
+    mode: Option<u32>,
+}
+
+impl MkdirTask {
+    pub fn new(path: PathBuf, mode: Option<u32>) -> Self {
+        Self { path, mode }
+    }
+}
+
+impl Task for MkdirTask {
+    fn execute(&self, _context: &mut RunContext) -> Result<()> {
+        std::fs::create_dir_all(&self.path).context(format!("Could not create {:?}", self.path))?;
+        if let Some(mode) = self.mode {
+            set_mode(&self.path, mode)?;
+        }
+        Ok(())
+    }
+
+    fn describe(&self) -> String {
+        format!("mkdir {}", self.path.display())
+    }
+}
+
+impl Display for MkdirTask {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+/// Writes text into a file or appends it to the file.
+#[derive(Debug)]
+pub struct WriteFileTask {
+    path: PathBuf,
+    content: String,
+    /// appends the content instead of replacing the file, the overwrite policy is not used then
+    append: bool,
+    overwrite: Overwrite,
+    mode: Option<u32>,
+}
+
+impl WriteFileTask {
+    pub fn new(path: PathBuf, content: String, overwrite: Overwrite, mode: Option<u32>) -> Self {
+        Self {
+            path,
+            content,
+            append: false,
+            overwrite,
+            mode,
+        }
+    }
+
+    pub fn append(path: PathBuf, content: String, mode: Option<u32>) -> Self {
+        Self {
+            path,
+            content,
+            append: true,
+            overwrite: Overwrite::Always,
+            mode,
+        }
+    }
+}
+
+impl Task for WriteFileTask {
+    fn execute(&self, context: &mut RunContext) -> Result<()> {
+        if self.append {
+            let mut file = std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)
+                .context(format!("Could not open {:?}", self.path))?;
+            file.write_all(self.content.as_bytes())
+                .context(format!("Could not write {:?}", self.path))?;
+        } else {
+            if exists(&self.path) && self.overwrite != Overwrite::Always {
+                match self.overwrite {
+                    Overwrite::Never => {
+                        context.message(&format!("{:?} exists, it is not written", self.path));
+                        return Ok(());
+                    }
+                    _ => bail!(format!("{:?} already exists", self.path)),
+                }
+            }
+            std::fs::write(&self.path, &self.content)
+                .context(format!("Could not write {:?}", self.path))?;
+        }
+        if let Some(mode) = self.mode {
+            set_mode(&self.path, mode)?;
+        }
+        Ok(())
+    }
+
+    fn describe(&self) -> String {
+        match self.append {
+            true => format!(
+                "append-file {} ({} bytes)",
+                self.path.display(),
+                self.content.len()
+            ),
+            false => format!(
+                "write-file {} ({} bytes)",
+                self.path.display(),
+                self.content.len()
+            ),
+        }
+    }
+}
+
+impl Display for WriteFileTask {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", mask::mask(&format!("{:?}", self)))
+    }
+}
+
+/// Sets the permissions of files and directories. The path can be a glob.
+#[derive(Debug)]
+pub struct ChmodTask {
+    path: PathBuf,
+    mode: u32,
+    /// also sets the permissions of the content of directories
+    recursive: bool,
+}
+
+impl ChmodTask {
+    pub fn new(path: PathBuf, mode: u32, recursive: bool) -> Self {
+        Self {
+            path,
+            mode,
+            recursive,
+        }
+    }
+
+    fn chmod(&self, path: &Path) -> Result<()> {
+        set_mode(path, self.mode)?;
+        if self.recursive && path.is_dir() {
+            for entry in std::fs::read_dir(path).context(format!("Could not read {:?}", path))? {
+                self.chmod(&entry.context(format!("Could not read {:?}", path))?.path())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Task for ChmodTask {
+    fn execute(&self, _context: &mut RunContext) -> Result<()> {
+        let paths = expand(&self.path)?;
+        if paths.is_empty() {
+            bail!(format!("{:?} does not match any file", self.path));
+        }
+        for path in paths {
+            self.chmod(&path)?;
+        }
+        Ok(())
+    }
+
+    fn describe(&self) -> String {
+        match self.recursive {
+            true => format!("chmod -R {:o} {}", self.mode, self.path.display()),
+            false => format!("chmod {:o} {}", self.mode, self.path.display()),
+        }
+    }
+}
+
+impl Display for ChmodTask {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+/// Creates a symlink at `dest` that points to `src`.
+#[derive(Debug)]
+pub struct SymlinkTask {
+    src: PathBuf,
+    dest: PathBuf,
+    overwrite: Overwrite,
+}
+
+impl SymlinkTask {
+    pub fn new(src: PathBuf, dest: PathBuf, overwrite: Overwrite) -> Self {
+        Self {
+            src,
+            dest,
+            overwrite,
+        }
+    }
+}
+
+impl Task for SymlinkTask {
+    fn execute(&self, context: &mut RunContext) -> Result<()> {
+        if !prepare_destination(&self.dest, self.overwrite)? {
+            context.message(&format!(
+                "{:?} exists, the symlink is not created",
+                self.dest
+            ));
+            return Ok(());
+        }
+        #[cfg(unix)]
+        let result = std::os::unix::fs::symlink(&self.src, &self.dest);
+        #[cfg(windows)]
+        let result = match self.src.is_dir() {
+            true => std::os::windows::fs::symlink_dir(&self.src, &self.dest),
+            false => std::os::windows::fs::symlink_file(&self.src, &self.dest),
+        };
+        result.context(format!(
+            "Could not create the symlink {:?} -> {:?}",
+            self.dest, self.src
+        ))
+    }
+
+    fn describe(&self) -> String {
+        format!("symlink {} -> {}", self.dest.display(), self.src.display())
+    }
+}
+
+impl Display for SymlinkTask {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CopyTask, MkdirTask, MoveTask, Overwrite, RemoveTask, SymlinkTask, WriteFileTask};
+    use crate::tasks::{RunContext, Task};
+    use std::path::Path;
+
+    fn read(path: &Path) -> String {
+        std::fs::read_to_string(path).unwrap()
+    }
+
+    #[test]
+    fn file_tasks_test() {
+        let dir = std::env::temp_dir().join(format!("workflowo-files-{}", std::process::id()));
+        let mut context = RunContext::default();
+        MkdirTask::new(dir.join("src/nested"), None)
+            .execute(&mut context)
+            .unwrap();
+        WriteFileTask::new(
+            dir.join("src/a.txt"),
+            "a".to_string(),
+            Overwrite::Error,
+            None,
+        )
+        .execute(&mut context)
+        .unwrap();
+        WriteFileTask::append(dir.join("src/a.txt"), "b".to_string(), None)
+            .execute(&mut context)
+            .unwrap();
+        assert_eq!(read(&dir.join("src/a.txt")), "ab");
+        WriteFileTask::new(
+            dir.join("src/b.txt"),
+            "b".to_string(),
+            Overwrite::Error,
+            None,
+        )
+        .execute(&mut context)
+        .unwrap();
+        WriteFileTask::new(
+            dir.join("src/nested/c.txt"),
+            "c".to_string(),
+            Overwrite::Error,
+            None,
+        )
+        .execute(&mut context)
+        .unwrap();
+        assert!(WriteFileTask::new(
+            dir.join("src/a.txt"),
+            "x".to_string(),
+            Overwrite::Error,
+            None
+        )
+        .execute(&mut context)
+        .is_err());
+
+        // a directory and a glob
+        MkdirTask::new(dir.join("dest"), None)
+            .execute(&mut context)
+            .unwrap();
+        CopyTask::new(dir.join("src"), dir.join("copy"), Overwrite::Error)
+            .execute(&mut context)
+            .unwrap();
+        assert_eq!(read(&dir.join("copy/nested/c.txt")), "c");
+        CopyTask::new(dir.join("src/*.txt"), dir.join("dest"), Overwrite::Error)
+            .execute(&mut context)
+            .unwrap();
+        assert_eq!(read(&dir.join("dest/b.txt")), "b");
+
+        // overwrite policies
+        std::fs::write(dir.join("src/b.txt"), "new").unwrap();
+        assert!(
+            CopyTask::new(dir.join("src/b.txt"), dir.join("dest"), Overwrite::Error)
+                .execute(&mut context)
+                .is_err()
+        );
+        CopyTask::new(dir.join("src/b.txt"), dir.join("dest"), Overwrite::Never)
+            .execute(&mut context)
+            .unwrap();
+        assert_eq!(read(&dir.join("dest/b.txt")), "b");
+        CopyTask::new(dir.join("src/b.txt"), dir.join("dest"), Overwrite::Always)
+            .execute(&mut context)
+            .unwrap();
+        assert_eq!(read(&dir.join("dest/b.txt")), "new");
+
+        MoveTask::new(
+            dir.join("dest/a.txt"),
+            dir.join("moved.txt"),
+            Overwrite::Error,
+        )
+        .execute(&mut context)
+        .unwrap();
+        assert!(!dir.join("dest/a.txt").exists());
+        assert_eq!(read(&dir.join("moved.txt")), "ab");
+        assert!(
+            MoveTask::new(dir.join("missing*.txt"), dir.join("dest"), Overwrite::Error)
+                .execute(&mut context)
+                .is_err()
+        );
+
+        SymlinkTask::new(dir.join("src"), dir.join("link"), Overwrite::Error)
+            .execute(&mut context)
+            .unwrap();
+        assert_eq!(read(&dir.join("link/b.txt")), "new");
+        RemoveTask::new(dir.join("link"))
+            .execute(&mut context)
+            .unwrap();
+        assert!(dir.join("src/b.txt").exists());
+
+        RemoveTask::new(dir.join("*"))
+            .execute(&mut context)
+            .unwrap();
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+        std::fs::remove_dir(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn chmod_test() {
+        use super::ChmodTask;
+        use std::os::unix::fs::PermissionsExt;
+        let dir = std::env::temp_dir().join(format!("workflowo-chmod-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("bin")).unwrap();
+        std::fs::write(dir.join("bin/run.sh"), "").unwrap();
+        let mode = |path: &Path| std::fs::metadata(path).unwrap().permissions().mode() & 0o777;
+
+        ChmodTask::new(dir.join("bin/*.sh"), 0o750, false)
+            .execute(&mut RunContext::default())
+            .unwrap();
+        assert_eq!(mode(&dir.join("bin/run.sh")), 0o750);
+        ChmodTask::new(dir.join("bin"), 0o700, true)
+            .execute(&mut RunContext::default())
+            .unwrap();
+        assert_eq!(mode(&dir.join("bin")), 0o700);
+        assert_eq!(mode(&dir.join("bin/run.sh")), 0o700);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
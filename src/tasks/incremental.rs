@@ -0,0 +1,183 @@
+use crate::history::data_dir;
+use crate::util::to_hex;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// The sources and outputs of a task. The task is skipped when the sources did not change since
+/// its last successful run and all outputs exist.
+#[derive(Debug)]
+pub struct Incremental {
+    /// glob patterns of the files the task reads
+    sources: Vec<String>,
+    /// the files or directories the task creates
+    outputs: Vec<PathBuf>,
+    /// the fingerprint taken by the last check, so the run does not take it again
+    checked: Mutex<Option<String>>,
+}
+
+/// What is stored about the last successful run of a task.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Record {
+    fingerprint: String,
+    /// the stdout of the task, if it is registered as a variable
+    pub registered: Option<String>,
+}
+
+impl Incremental {
+    pub fn new(sources: Vec<String>, outputs: Vec<PathBuf>) -> Self {
+        Self {
+            sources,
+            outputs,
+            checked: Mutex::new(None),
+        }
+    }
+
+    /// Returns the hash of the paths and contents of all source files.
+    pub fn fingerprint(&self) -> Result<String> {
+        let mut files = Vec::new();
+        for pattern in &self.sources {
+            for path in glob::glob(pattern).context(format!("invalid glob {}", pattern))? {
+                let path = path.context(format!("could not read path of glob {}", pattern))?;
+                if path.is_file() {
+                    files.push(path);
+                }
+            }
+        }
+        files.sort();
+        files.dedup();
+
+        let mut hasher = Sha256::new();
+        for file in files {
+            hasher.update(file.to_string_lossy().as_bytes());
+            hasher.update([0]);
+            hasher.update(std::fs::read(&file).context(format!("could not read {:?}", file))?);
+            hasher.update([0]);
+        }
+        Ok(to_hex(&hasher.finalize()))
+    }
+
+    /// Returns the file the fingerprint of the task is stored in. The task is identified by its
+    /// description, sources and outputs.
+    fn cache_file(&self, task: &str) -> Result<PathBuf> {
+        let mut hasher = Sha256::new();
+        hasher.update(task.as_bytes());
+        for source in &self.sources {
+            hasher.update([0]);
+            hasher.update(source.as_bytes());
+        }
+        for output in &self.outputs {
+            hasher.update([1]);
+            hasher.update(output.to_string_lossy().as_bytes());
+        }
+        Ok(data_dir()?
+            .join("fingerprints")
+            .join(to_hex(&hasher.finalize())))
+    }
+
+    /// Returns the record of the last successful run if the outputs exist and the fingerprint of
+    /// the sources did not change since then. Otherwise the fingerprint is kept for the run.
+    pub fn up_to_date(&self, task: &str) -> Result<Option<Record>> {
+        let fingerprint = self.fingerprint()?;
+        let cache_file = self.cache_file(task)?;
+        if self.outputs.iter().all(|output| output.exists()) && cache_file.exists() {
+            let stored = std::fs::read_to_string(&cache_file)
+                .context(format!("could not read {:?}", cache_file))?;
+            // records in an older format count as changed
+            if let Ok(record) = serde_json::from_str::<Record>(&stored) {
+                if record.fingerprint == fingerprint {
+                    return Ok(Some(record));
+                }
+            }
+        }
+        *self.checked.lock().unwrap() = Some(fingerprint);
+        Ok(None)
+    }
+
+    /// Returns the fingerprint taken by `up_to_date`, or takes it if the task was not checked.
+    /// It has to be taken before the run, so sources changed while running are detected by the
+    /// next run.
+    pub fn take_fingerprint(&self) -> Result<String> {
+        match self.checked.lock().unwrap().take() {
+            Some(fingerprint) => Ok(fingerprint),
+            None => self.fingerprint(),
+        }
+    }
+
+    /// Stores the fingerprint of the sources and the registered stdout after a successful run.
+    pub fn store(&self, task: &str, fingerprint: String, registered: Option<String>) -> Result<()> {
+        let cache_file = self.cache_file(task)?;
+        if let Some(dir) = cache_file.parent() {
+            std::fs::create_dir_all(dir).context(format!("could not create {:?}", dir))?;
+        }
+        let record = Record {
+            fingerprint,
+            registered,
+        };
+        std::fs::write(&cache_file, serde_json::to_string(&record)?)
+            .context(format!("could not write {:?}", cache_file))
+    }
+
+    pub fn describe(&self) -> String {
+        format!("(sources {})", self.sources.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Incremental;
+
+    #[test]
+    fn fingerprint_test() {
+        let dir =
+            std::env::temp_dir().join(format!("workflowo-incremental-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.c"), "a").unwrap();
+        std::fs::write(dir.join("b.c"), "b").unwrap();
+        std::fs::write(dir.join("b.h"), "h").unwrap();
+
+        let incremental = Incremental::new(
+            vec![dir.join("*.c").to_string_lossy().into_owned()],
+            Vec::new(),
+        );
+        let first = incremental.fingerprint().unwrap();
+        std::fs::write(dir.join("b.h"), "changed").unwrap();
+        assert_eq!(incremental.fingerprint().unwrap(), first);
+        std::fs::write(dir.join("b.c"), "changed").unwrap();
+        assert_ne!(incremental.fingerprint().unwrap(), first);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn up_to_date_test() {
+        let dir = std::env::temp_dir().join(format!("workflowo-up-to-date-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::env::set_var("WORKFLOWO_DATA_DIR", dir.join("data"));
+        std::fs::write(dir.join("a.c"), "a").unwrap();
+
+        let incremental = Incremental::new(
+            vec![dir.join("*.c").to_string_lossy().into_owned()],
+            vec![dir.join("a.c")],
+        );
+        assert!(incremental.up_to_date("bash: make").unwrap().is_none());
+        let fingerprint = incremental.take_fingerprint().unwrap();
+        assert_eq!(fingerprint, incremental.fingerprint().unwrap());
+        incremental
+            .store("bash: make", fingerprint, Some("built".to_string()))
+            .unwrap();
+
+        let record = incremental.up_to_date("bash: make").unwrap().unwrap();
+        assert_eq!(record.registered.as_deref(), Some("built"));
+        assert!(incremental
+            .up_to_date("bash: make clean")
+            .unwrap()
+            .is_none());
+        std::fs::write(dir.join("a.c"), "changed").unwrap();
+        assert!(incremental.up_to_date("bash: make").unwrap().is_none());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
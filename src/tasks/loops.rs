@@ -0,0 +1,491 @@
+use super::{print_dry_run, RunContext, Task};
+use anyhow::{bail, Context, Result};
+use serde_yaml::Value;
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+use std::sync::Arc;
+
+/// Creates a task out of its definition with the given variables.
+///
+/// Loops can only create their tasks when running, because the variables are not known before.
+pub type TaskFactory =
+    Arc<dyn Fn(&Value, &HashMap<String, Value>) -> Result<Box<dyn Task>> + Send + Sync>;
+
+/// The values a [`ForEach`] iterates over.
+#[derive(Debug)]
+pub enum Items {
+    /// a fixed list of values
+    List(Vec<Value>),
+    /// all paths that match a glob pattern
+    Glob(String),
+    /// the lines of a registered variable
+    Lines(String),
+}
+
+impl Items {
+    fn resolve(&self, context: &RunContext) -> Result<Vec<Value>> {
+        match self {
+            Self::List(values) => Ok(values.to_owned()),
+            Self::Glob(pattern) => {
+                let mut paths = Vec::new();
+                for path in glob::glob(pattern).context(format!("invalid glob {}", pattern))? {
+                    let path = path.context(format!("could not read path of glob {}", pattern))?;
+                    paths.push(Value::String(path.to_string_lossy().into_owned()));
+                }
+                Ok(paths)
+            }
+            Self::Lines(variable) => match context.variables.get(variable) {
+                Some(Value::String(text)) => Ok(text
+                    .lines()
+                    .filter(|line| !line.trim().is_empty())
+                    .map(|line| Value::String(line.to_string()))
+                    .collect()),
+                Some(other) => bail!(format!(
+                    "variable {} is not a string: {:?}",
+                    variable, other
+                )),
+                None => bail!(format!("variable {} is not registered", variable)),
+            },
+        }
+    }
+}
+
+/// Creates the tasks out of their definitions and executes them in order.
+fn execute_definitions(
+    factory: &TaskFactory,
+    definitions: &[Value],
+    variables: &HashMap<String, Value>,
+    context: &mut RunContext,
+) -> Result<()> {
+    for (index, definition) in definitions.iter().enumerate() {
+        let task = factory(definition, variables)
+            .context(format!("Could not create child {}(first is 0)", index))?;
+        context
+            .run_child(index, task.as_ref())
+            .context(format!("Child {}(first is 0) failed", index))?;
+    }
+    Ok(())
+}
+
+/// Prints the dry-run of the tasks created out of their definitions.
+fn dry_run_definitions(
+    factory: &TaskFactory,
+    definitions: &[Value],
+    variables: &HashMap<String, Value>,
+    context: &mut RunContext,
+    depth: usize,
+) -> Result<()> {
+    for (index, definition) in definitions.iter().enumerate() {
+        let task = factory(definition, variables)
+            .context(format!("Could not create child {}(first is 0)", index))?;
+        task.dry_run(context, depth)?;
+    }
+    Ok(())
+}
+
+/// Executes its tasks once for every item. The item is available as a variable.
+pub struct ForEach {
+    items: Items,
+    variable: String,
+    tasks: Vec<Value>,
+    factory: TaskFactory,
+}
+
+impl ForEach {
+    pub fn new(items: Items, variable: String, tasks: Vec<Value>, factory: TaskFactory) -> Self {
+        Self {
+            items,
+            variable,
+            tasks,
+            factory,
+        }
+    }
+}
+
+impl Task for ForEach {
+    fn execute(&self, context: &mut RunContext) -> Result<()> {
+        let items = self
+            .items
+            .resolve(context)
+            .context("Could not resolve the items of foreach")?;
+
+        for (index, item) in items.into_iter().enumerate() {
+            let mut variables = context.variables.clone();
+            variables.insert(self.variable.clone(), item.clone());
+            context
+                .scoped(index, |context| {
+                    execute_definitions(&self.factory, &self.tasks, &variables, context)
+                })
+                .context(format!(
+                    "Iteration {}(first is 0) of foreach with {:?} failed",
+                    index, item
+                ))?;
+        }
+        Ok(())
+    }
+
+    fn describe(&self) -> String {
+        match &self.items {
+            Items::List(values) => format!("foreach {} in {} items", self.variable, values.len()),
+            Items::Glob(pattern) => format!("foreach {} in glob {}", self.variable, pattern),
+            Items::Lines(variable) => {
+                format!("foreach {} in lines of {}", self.variable, variable)
+            }
+        }
+    }
+
+    fn dry_run(&self, context: &mut RunContext, depth: usize) -> Result<()> {
+        print_dry_run(depth, &self.describe());
+
+        // registered variables are not known without running
+        if let Items::Lines(variable) = &self.items {
+            if !context.variables.contains_key(variable) {
+                print_dry_run(
+                    depth + 1,
+                    &format!("{} is only known when running", variable),
+                );
+                return Ok(());
+            }
+        }
+
+        let items = self
+            .items
+            .resolve(context)
+            .context("Could not resolve the items of foreach")?;
+        for item in items {
+            print_dry_run(
+                depth + 1,
+                &format!("{} = {}", self.variable, describe_value(&item)),
+            );
+            let mut variables = context.variables.clone();
+            variables.insert(self.variable.clone(), item);
+            dry_run_definitions(&self.factory, &self.tasks, &variables, context, depth + 2)?;
+        }
+        Ok(())
+    }
+}
+
+/// Formats a value of a variable for the dry-run.
+fn describe_value(value: &Value) -> String {
+    match value {
+        Value::String(string) => string.to_owned(),
+        other => serde_yaml::to_string(other)
+            .unwrap_or_else(|_| format!("{:?}", other))
+            .trim_end()
+            .replace('\n', ", "),
+    }
+}
+
+impl Display for ForEach {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "ForEach: {{ items {:?} as \"{}\" tasks {:?} }}",
+            self.items, self.variable, self.tasks
+        )
+    }
+}
+
+/// Executes its tasks for every combination of the values of its axes.
+pub struct Matrix {
+    axes: Vec<(String, Vec<Value>)>,
+    /// the number of combinations that run at the same time, 1 runs them in order
+    parallel: usize,
+    tasks: Vec<Value>,
+    factory: TaskFactory,
+}
+
+impl Matrix {
+    pub fn new(
+        axes: Vec<(String, Vec<Value>)>,
+        parallel: usize,
+        tasks: Vec<Value>,
+        factory: TaskFactory,
+    ) -> Self {
+        Self {
+            axes,
+            parallel,
+            tasks,
+            factory,
+        }
+    }
+
+    /// Returns the cartesian product of all axes.
+    fn combinations(&self) -> Vec<HashMap<String, Value>> {
+        let mut combinations = vec![HashMap::new()];
+        for (name, values) in &self.axes {
+            let mut new_combinations = Vec::new();
+            for combination in &combinations {
+                for value in values {
+                    let mut new_combination = combination.clone();
+                    new_combination.insert(name.clone(), value.clone());
+                    new_combinations.push(new_combination);
+                }
+            }
+            combinations = new_combinations;
+        }
+        combinations
+    }
+}
+
+impl Task for Matrix {
+    fn execute(&self, context: &mut RunContext) -> Result<()> {
+        let combinations = self.combinations();
+
+        if self.parallel <= 1 {
+            for (index, combination) in combinations.into_iter().enumerate() {
+                let mut variables = context.variables.clone();
+                variables.extend(combination.clone());
+                context
+                    .scoped(index, |context| {
+                        execute_definitions(&self.factory, &self.tasks, &variables, context)
+                    })
+                    .context(format!("Combination {:?} of matrix failed", combination))?;
+            }
+            return Ok(());
+        }
+
+        // the combinations run in batches, every combination of a batch runs in its own thread
+        // with its own copy of the context
+        let combinations: Vec<_> = combinations.into_iter().enumerate().collect();
+        let mut results: Vec<(HashMap<String, Value>, Result<()>)> = Vec::new();
+        for batch in combinations.chunks(self.parallel) {
+            std::thread::scope(|scope| {
+                let handles: Vec<_> = batch
+                    .iter()
+                    .map(|(index, combination)| {
+                        let mut thread_context = context.clone();
+                        scope.spawn(move || {
+                            let mut variables = thread_context.variables.clone();
+                            variables.extend(combination.clone());
+                            let result = thread_context.scoped(*index, |context| {
+                                execute_definitions(&self.factory, &self.tasks, &variables, context)
+                            });
+                            (combination.clone(), result)
+                        })
+                    })
+                    .collect();
+                results.extend(
+                    handles
+                        .into_iter()
+                        .map(|handle| handle.join().expect("matrix thread panicked")),
+                );
+            });
+        }
+
+        let mut failed = Vec::new();
+        for (combination, result) in results {
+            if let Err(error) = result {
+                failed.push(format!("{:?}: {:#}", combination, error));
+            }
+        }
+        if !failed.is_empty() {
+            bail!(format!(
+                "{} combinations of matrix failed:\n{}",
+                failed.len(),
+                failed.join("\n")
+            ));
+        }
+        Ok(())
+    }
+
+    fn describe(&self) -> String {
+        let axes: Vec<&str> = self.axes.iter().map(|(name, _)| name.as_str()).collect();
+        format!(
+            "matrix over {} ({} combinations{})",
+            axes.join(", "),
+            self.combinations().len(),
+            if self.parallel > 1 {
+                format!(", {} in parallel", self.parallel)
+            } else {
+                String::new()
+            }
+        )
+    }
+
+    fn dry_run(&self, context: &mut RunContext, depth: usize) -> Result<()> {
+        print_dry_run(depth, &self.describe());
+        for combination in self.combinations() {
+            let mut names: Vec<&String> = combination.keys().collect();
+            names.sort();
+            let description: Vec<String> = names
+                .into_iter()
+                .map(|name| format!("{} = {}", name, describe_value(&combination[name])))
+                .collect();
+            print_dry_run(depth + 1, &description.join(", "));
+            let mut variables = context.variables.clone();
+            variables.extend(combination);
+            dry_run_definitions(&self.factory, &self.tasks, &variables, context, depth + 2)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for Matrix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Matrix: {{ axes {:?} parallel {} tasks {:?} }}",
+            self.axes, self.parallel, self.tasks
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{describe_value, ForEach, Items, Matrix, TaskFactory};
+    use crate::tasks::{RunContext, Task};
+    use anyhow::{bail, Result};
+    use serde_yaml::Value;
+    use std::fmt;
+    use std::sync::{Arc, Mutex};
+
+    /// Records the values of its variables when executed and fails if one of them is `fail`.
+    struct Record {
+        values: Vec<String>,
+        executed: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Task for Record {
+        fn execute(&self, _context: &mut RunContext) -> Result<()> {
+            self.executed.lock().unwrap().push(self.values.join(" "));
+            if self.values.iter().any(|value| value == "fail") {
+                bail!("failed");
+            }
+            Ok(())
+        }
+
+        fn describe(&self) -> String {
+            self.values.join(" ")
+        }
+    }
+
+    impl fmt::Display for Record {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "{}", self.describe())
+        }
+    }
+
+    /// Creates tasks that record the variables named in their definition, e.g. `a b`.
+    fn record_factory(executed: &Arc<Mutex<Vec<String>>>) -> TaskFactory {
+        let executed = executed.clone();
+        Arc::new(move |definition, variables| {
+            let names = match definition {
+                Value::String(names) => names,
+                _ => bail!("definition is not a string"),
+            };
+            let mut values = Vec::new();
+            for name in names.split_whitespace() {
+                match variables.get(name) {
+                    Some(value) => values.push(describe_value(value)),
+                    None => bail!(format!("variable {} is not set", name)),
+                }
+            }
+            Ok(Box::new(Record {
+                values,
+                executed: executed.clone(),
+            }))
+        })
+    }
+
+    fn foreach(items: Items, executed: &Arc<Mutex<Vec<String>>>) -> ForEach {
+        ForEach::new(
+            items,
+            "item".to_string(),
+            vec!["item".into()],
+            record_factory(executed),
+        )
+    }
+
+    fn matrix(parallel: usize, executed: &Arc<Mutex<Vec<String>>>) -> Matrix {
+        Matrix::new(
+            vec![
+                ("a".to_string(), vec![1.into(), 2.into()]),
+                ("b".to_string(), vec!["x".into(), "y".into(), "z".into()]),
+            ],
+            parallel,
+            vec!["a b".into()],
+            record_factory(executed),
+        )
+    }
+
+    #[test]
+    fn matrix_combinations_test() {
+        let combinations = matrix(1, &Arc::default()).combinations();
+        assert_eq!(combinations.len(), 6);
+        assert_eq!(combinations[5]["a"], Value::from(2));
+        assert_eq!(combinations[5]["b"], Value::from("z"));
+    }
+
+    #[test]
+    fn foreach_test() {
+        let executed = Arc::new(Mutex::new(Vec::new()));
+        let mut context = RunContext::default();
+
+        foreach(Items::List(vec!["a".into(), 1.into()]), &executed)
+            .execute(&mut context)
+            .unwrap();
+        assert_eq!(*executed.lock().unwrap(), ["a", "1"]);
+
+        let dir = std::env::temp_dir().join(format!("workflowo-foreach-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("b.txt"), "").unwrap();
+        std::fs::write(dir.join("a.txt"), "").unwrap();
+        std::fs::write(dir.join("c.md"), "").unwrap();
+        executed.lock().unwrap().clear();
+        let pattern = dir.join("*.txt").to_string_lossy().into_owned();
+        foreach(Items::Glob(pattern), &executed)
+            .execute(&mut context)
+            .unwrap();
+        assert_eq!(
+            *executed.lock().unwrap(),
+            [dir.join("a.txt"), dir.join("b.txt")].map(|path| path.to_string_lossy().into_owned())
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        executed.lock().unwrap().clear();
+        context
+            .variables
+            .insert("files".to_string(), Value::from("x.txt\n\ny.txt\n"));
+        foreach(Items::Lines("files".to_string()), &executed)
+            .execute(&mut context)
+            .unwrap();
+        assert_eq!(*executed.lock().unwrap(), ["x.txt", "y.txt"]);
+
+        let missing = foreach(Items::Lines("missing".to_string()), &executed);
+        assert!(missing.execute(&mut context).is_err());
+    }
+
+    #[test]
+    fn parallel_matrix_test() {
+        let executed = Arc::new(Mutex::new(Vec::new()));
+        for parallel in [2, 4, 6] {
+            executed.lock().unwrap().clear();
+            matrix(parallel, &executed)
+                .execute(&mut RunContext::default())
+                .unwrap();
+            let mut executed = executed.lock().unwrap().clone();
+            executed.sort();
+            assert_eq!(executed, ["1 x", "1 y", "1 z", "2 x", "2 y", "2 z"]);
+        }
+    }
+
+    #[test]
+    fn parallel_matrix_failure_test() {
+        let executed = Arc::new(Mutex::new(Vec::new()));
+        let matrix = Matrix::new(
+            vec![("a".to_string(), vec!["ok".into(), "fail".into()])],
+            2,
+            vec!["a".into()],
+            record_factory(&executed),
+        );
+        let error = matrix
+            .execute(&mut RunContext::default())
+            .unwrap_err()
+            .to_string();
+        assert!(error.starts_with("1 combinations of matrix failed"));
+        assert!(error.contains("fail"));
+        // the other combinations still run
+        assert_eq!(executed.lock().unwrap().len(), 2);
+    }
+}
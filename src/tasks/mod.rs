@@ -1,16 +1,268 @@
-use std::{env, fmt, fmt::Display};
+use crate::events::{Event, EventEmitter, TaskOutput};
+use crate::mask;
+use std::{
+    collections::{HashMap, HashSet},
+    env, fmt,
+    fmt::Display,
+    sync::atomic::{AtomicU8, Ordering},
+    sync::Arc,
+    time::Instant,
+};
+pub mod approve;
+pub mod archive;
+pub mod files;
+pub mod incremental;
+pub mod loops;
 pub mod shell;
 pub mod ssh;
-use anyhow::{Context, Result};
+pub mod template;
+use anyhow::{bail, Context, Result};
+use serde_yaml::Value;
+
+const RUNNING: u8 = 0;
+const CANCELLED: u8 = 1;
+const FORCED: u8 = 2;
+
+/// Cancels a run. Clones share the state.
+///
+/// A cancelled run interrupts its processes and stops before the next task. A forced run kills
+/// its processes.
+#[derive(Debug, Clone)]
+pub struct CancelToken {
+    state: Arc<AtomicU8>,
+    /// the state from which on the run counts as cancelled
+    threshold: u8,
+}
+
+impl Default for CancelToken {
+    fn default() -> Self {
+        Self {
+            state: Arc::new(AtomicU8::new(RUNNING)),
+            threshold: CANCELLED,
+        }
+    }
+}
+
+impl CancelToken {
+    pub fn cancel(&self) {
+        self.state.fetch_max(CANCELLED, Ordering::SeqCst);
+    }
+
+    pub fn force(&self) {
+        self.state.fetch_max(FORCED, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.state.load(Ordering::SeqCst) >= self.threshold
+    }
+
+    pub fn is_forced(&self) -> bool {
+        self.state.load(Ordering::SeqCst) >= FORCED
+    }
+
+    /// Returns a token for cleanup tasks that only counts as cancelled when the run is forced.
+    pub fn cleanup(&self) -> Self {
+        Self {
+            state: self.state.clone(),
+            threshold: FORCED,
+        }
+    }
+}
+
+/// State that is shared between the tasks of a run.
+#[derive(Debug, Default, Clone)]
+pub struct RunContext {
+    /// Variables that are set while running. For example the registered output of a task.
+    pub variables: HashMap<String, Value>,
+    /// Receives the events of the run.
+    pub events: EventEmitter,
+    /// Prints messages to stderr, because stdout is used for something else (e.g. json events).
+    pub messages_to_stderr: bool,
+    /// The tasks that are executed.
+    pub selection: Selection,
+    /// Executes tasks even if their sources did not change.
+    pub force: bool,
+    /// Approves `approve` tasks without asking.
+    pub approve: bool,
+    /// Prints which tasks are skipped.
+    pub verbose: bool,
+    /// Stops the run before the next task and interrupts running processes.
+    pub cancel: CancelToken,
+    /// the path of the task that is executed right now
+    path: Vec<usize>,
+    /// the tasks of a `finally` block are executed, they ignore the selection
+    cleanup: bool,
+    /// the output of the task that is executed right now
+    output: Option<TaskOutput>,
+}
+
+impl RunContext {
+    /// Returns the path of the task that is executed right now, e.g. `2.0.1`.
+    pub fn path(&self) -> String {
+        path_to_string(&self.path)
+    }
+
+    /// Records the exit code and output of the task that is executed right now for its event.
+    pub fn record_output(&mut self, output: TaskOutput) {
+        self.output = Some(output);
+    }
+
+    /// Prints a message for the user. Secrets are masked.
+    pub fn message(&self, text: &str) {
+        let text = mask::mask(text);
+        if self.messages_to_stderr {
+            eprintln!("{}", text);
+        } else {
+            println!("{}", text);
+        }
+    }
+
+    /// Executes a child task and emits the events for it.
+    pub fn run_child(&mut self, index: usize, child: &dyn Task) -> Result<()> {
+        if self.cancel.is_cancelled() {
+            bail!("the run was cancelled");
+        }
+        self.path.push(index);
+        let path = self.path();
+        let task = child.describe();
+
+        let selection_reason = match self.cleanup {
+            true => None,
+            false => self.selection.skip_reason(&self.path),
+        };
+        if let Some(reason) = selection_reason.or_else(|| child.skip_reason(self)) {
+            if self.verbose {
+                self.message(&format!("Skipping {} {}: {}", path, task, reason));
+            }
+            self.events.emit(Event::TaskSkipped { path, task, reason });
+            self.path.pop();
+            return Ok(());
+        }
+
+        self.events.emit(Event::TaskStarted {
+            path: path.clone(),
+            task: task.clone(),
+        });
+
+        self.output = None;
+        let start = Instant::now();
+        let result = child.execute(self);
+        let duration_ms = start.elapsed().as_millis() as u64;
+        let output = self.output.take().unwrap_or_default();
+
+        self.events.emit(match &result {
+            Ok(()) => Event::TaskFinished {
+                path,
+                task,
+                duration_ms,
+                output,
+                cleanup: self.cleanup,
+            },
+            Err(error) => Event::TaskFailed {
+                path,
+                task,
+                duration_ms,
+                output,
+                error: error.chain().map(|cause| cause.to_string()).collect(),
+            },
+        });
+        self.path.pop();
+        result
+    }
+
+    /// Executes the function one level deeper in the task path.
+    /// Used by tasks that execute their children multiple times.
+    pub fn scoped<T>(&mut self, index: usize, function: impl FnOnce(&mut Self) -> T) -> T {
+        self.path.push(index);
+        let result = function(self);
+        self.path.pop();
+        result
+    }
+}
+
+/// Parses a task path like `2.0.1`.
+pub fn parse_task_path(text: &str) -> Result<Vec<usize>> {
+    text.split('.')
+        .map(|index| {
+            index
+                .parse::<usize>()
+                .context(format!("{} is not a valid task path", text))
+        })
+        .collect()
+}
+
+fn path_to_string(path: &[usize]) -> String {
+    path.iter()
+        .map(|index| index.to_string())
+        .collect::<Vec<String>>()
+        .join(".")
+}
+
+/// Selects the tasks of a job that are executed.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub enum Selection {
+    /// every task
+    #[default]
+    All,
+    /// every task except the tasks with these paths, because they completed in the last run
+    Resume(HashSet<String>),
+    /// the task with the path and every task after it
+    From(Vec<usize>),
+    /// the task with the path
+    Only(Vec<usize>),
+}
+
+impl Selection {
+    /// Returns why the task with the path is not selected.
+    ///
+    /// The parents of a selected task are always selected, so the task can be reached.
+    pub fn skip_reason(&self, path: &[usize]) -> Option<String> {
+        match self {
+            Self::All => None,
+            Self::Resume(completed) => completed
+                .contains(&path_to_string(path))
+                .then(|| "completed in the last run".to_string()),
+            Self::From(from) => (!from.starts_with(path) && path < from.as_slice())
+                .then(|| format!("before {}", path_to_string(from))),
+            Self::Only(only) => (!only.starts_with(path) && !path.starts_with(only))
+                .then(|| format!("only {} is selected", path_to_string(only))),
+        }
+    }
+}
 
 pub trait Task: Display {
     /// Will be called when the task should be executed.
-    fn execute(&self) -> Result<()>;
+    fn execute(&self, context: &mut RunContext) -> Result<()>;
+
+    /// Describes in one line what the task would do. Secrets like passwords are not included.
+    fn describe(&self) -> String;
+
+    /// Returns why the task will not be executed, if it is skipped. A skipped task may still set
+    /// the variables it would register.
+    fn skip_reason(&self, _context: &mut RunContext) -> Option<String> {
+        None
+    }
+
+    /// Prints what would be executed without executing anything.
+    ///
+    /// Tasks with children print their children one level deeper.
+    fn dry_run(&self, _context: &mut RunContext, depth: usize) -> Result<()> {
+        print_dry_run(depth, &self.describe());
+        Ok(())
+    }
+}
+
+/// Prints one line of the dry-run output indented by the depth.
+pub fn print_dry_run(depth: usize, text: &str) {
+    println!("{}{}", "    ".repeat(depth), mask::mask(text));
 }
 
 pub struct Job {
     pub name: String,
     children: Vec<Box<dyn Task>>,
+    /// cleanup tasks that are executed after the children, even if a child failed or the run
+    /// was cancelled
+    finally: Vec<Box<dyn Task>>,
 }
 
 impl Job {
@@ -18,21 +270,92 @@ impl Job {
         Self {
             name,
             children: Vec::new(),
+            finally: Vec::new(),
         }
     }
 
     pub fn add_child(&mut self, child: Box<dyn Task>) {
         self.children.push(child);
     }
+
+    pub fn add_finally(&mut self, task: Box<dyn Task>) {
+        self.finally.push(task);
+    }
+
+    /// Executes all cleanup tasks. Only a forced cancel stops them.
+    /// Their paths follow the paths of the children. They are executed regardless of the
+    /// selection and are not stored in checkpoints, so a resumed job cleans up again.
+    fn execute_finally(&self, context: &mut RunContext) -> Result<()> {
+        let cancel = context.cancel.clone();
+        let cleanup = context.cleanup;
+        context.cancel = cancel.cleanup();
+        context.cleanup = true;
+        let mut result = Ok(());
+        for (index, task) in self.finally.iter().enumerate() {
+            let task_result = context
+                .run_child(self.children.len() + index, task.as_ref())
+                .context(format!(
+                    "Cleanup task {}(first is 0) of job {} failed",
+                    index, &self.name
+                ));
+            if result.is_ok() {
+                result = task_result;
+            }
+        }
+        context.cancel = cancel;
+        context.cleanup = cleanup;
+        result
+    }
+
+    /// Executes the job as the root of a run and emits the events for the job.
+    pub fn run(&self, context: &mut RunContext) -> Result<()> {
+        context.events.emit(Event::JobStarted {
+            job: self.name.clone(),
+        });
+        let start = Instant::now();
+        let result = self.execute(context);
+        context.events.emit(Event::JobFinished {
+            job: self.name.clone(),
+            duration_ms: start.elapsed().as_millis() as u64,
+            success: result.is_ok(),
+        });
+        result
+    }
 }
 
 impl Task for Job {
-    fn execute(&self) -> Result<()> {
+    fn execute(&self, context: &mut RunContext) -> Result<()> {
+        let mut result = Ok(());
         for (index, child) in self.children.iter().enumerate() {
-            child.execute().context(format!(
+            result = context.run_child(index, child.as_ref()).context(format!(
                 "Child {}(first is 0) of task {} failed",
                 index, &self.name
-            ))?;
+            ));
+            if result.is_err() {
+                break;
+            }
+        }
+        if self.finally.is_empty() {
+            return result;
+        }
+        let finally_result = self.execute_finally(context);
+        result.and(finally_result)
+    }
+
+    fn describe(&self) -> String {
+        format!("job {}", self.name)
+    }
+
+    fn dry_run(&self, context: &mut RunContext, depth: usize) -> Result<()> {
+        print_dry_run(depth, &self.describe());
+        for child in &self.children {
+            child.dry_run(context, depth + 1)?;
+        }
+        if !self.finally.is_empty() {
+            print_dry_run(depth + 1, "finally");
+            for task in &self.finally {
+                task.dry_run(context, depth + 2)?;
+            }
         }
         Ok(())
     }
@@ -44,7 +367,15 @@ impl Display for Job {
         for child in &self.children {
             text += &format!("{} ", child);
         }
-        text += "} }";
+        text += "} ";
+        if !self.finally.is_empty() {
+            text += "finally { ";
+            for task in &self.finally {
+                text += &format!("{} ", task);
+            }
+            text += "} ";
+        }
+        text += "}";
 
         write!(f, "{}", text)
     }
@@ -74,31 +405,54 @@ impl OSDependent {
     }
 }
 
-impl Task for OSDependent {
-    fn execute(&self) -> Result<()> {
+impl OSDependent {
+    /// Checks if the program is running on the target os.
+    fn is_target_os(&self) -> bool {
         match self.os {
-            OS::Windows => {
-                if env::consts::OS != "windows" {
-                    // return if not target os
-                    return Ok(());
-                }
-            }
-            OS::Linux => {
-                if env::consts::OS != "linux" {
-                    // return if not target os
-                    return Ok(());
-                }
-            }
+            OS::Windows => env::consts::OS == "windows",
+            OS::Linux => env::consts::OS == "linux",
+        }
+    }
+}
+
+impl Task for OSDependent {
+    fn execute(&self, context: &mut RunContext) -> Result<()> {
+        if !self.is_target_os() {
+            // return if not target os
+            return Ok(());
         }
 
         for (index, child) in self.children.iter().enumerate() {
-            child.execute().context(format!(
+            context.run_child(index, child.as_ref()).context(format!(
                 "Child task {}(first is 0) of OsDependent {:?} failed",
                 index, self.os
             ))?;
         }
         Ok(())
     }
+
+    fn describe(&self) -> String {
+        format!("on {:?}", self.os)
+    }
+
+    fn skip_reason(&self, _context: &mut RunContext) -> Option<String> {
+        match self.is_target_os() {
+            true => None,
+            false => Some(format!("not running on {:?}", self.os)),
+        }
+    }
+
+    fn dry_run(&self, context: &mut RunContext, depth: usize) -> Result<()> {
+        if !self.is_target_os() {
+            // nothing would be executed if not target os
+            return Ok(());
+        }
+        print_dry_run(depth, &self.describe());
+        for child in &self.children {
+            child.dry_run(context, depth + 1)?;
+        }
+        Ok(())
+    }
 }
 
 impl Display for OSDependent {
@@ -125,10 +479,18 @@ impl PrintTask {
 }
 
 impl Task for PrintTask {
-    fn execute(&self) -> Result<()> {
-        println!("{}", self.prompt);
+    fn execute(&self, context: &mut RunContext) -> Result<()> {
+        context.message(&self.prompt);
+        context.record_output(TaskOutput {
+            stdout: Some(self.prompt.clone()),
+            ..Default::default()
+        });
         Ok(())
     }
+
+    fn describe(&self) -> String {
+        format!("print {:?}", self.prompt)
+    }
 }
 
 impl Display for PrintTask {
@@ -136,3 +498,133 @@ impl Display for PrintTask {
         write!(f, "{:?}", self)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Job, RunContext, Selection, Task};
+    use crate::checkpoint::{CheckpointSink, CheckpointStore};
+    use anyhow::{bail, Result};
+    use std::collections::HashSet;
+    use std::fmt;
+    use std::path::Path;
+    use std::sync::{Arc, Mutex};
+
+    /// Records its name when executed. Cancels the run if it is named `cancel` and fails if it
+    /// is named `fail`.
+    struct Record {
+        name: &'static str,
+        executed: Arc<Mutex<Vec<&'static str>>>,
+    }
+
+    impl Task for Record {
+        fn execute(&self, context: &mut RunContext) -> Result<()> {
+            self.executed.lock().unwrap().push(self.name);
+            if self.name == "cancel" {
+                context.cancel.cancel();
+                bail!("cancelled");
+            }
+            if self.name == "fail" {
+                bail!("failed");
+            }
+            Ok(())
+        }
+
+        fn describe(&self) -> String {
+            self.name.to_string()
+        }
+    }
+
+    impl fmt::Display for Record {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "{}", self.name)
+        }
+    }
+
+    #[test]
+    fn finally_test() {
+        let executed = Arc::new(Mutex::new(Vec::new()));
+        let record = |name| {
+            Box::new(Record {
+                name,
+                executed: executed.clone(),
+            })
+        };
+        let mut job = Job::new("job".to_string());
+        job.add_child(record("first"));
+        job.add_child(record("cancel"));
+        job.add_child(record("skipped"));
+        job.add_finally(record("cleanup"));
+
+        let mut context = RunContext::default();
+        assert!(job.run(&mut context).is_err());
+        assert!(context.cancel.is_cancelled());
+        assert_eq!(*executed.lock().unwrap(), ["first", "cancel", "cleanup"]);
+    }
+
+    #[test]
+    fn resume_finally_test() {
+        let executed = Arc::new(Mutex::new(Vec::new()));
+        let record = |name| {
+            Box::new(Record {
+                name,
+                executed: executed.clone(),
+            })
+        };
+        let mut job = Job::new("job".to_string());
+        job.add_child(record("first"));
+        job.add_child(record("fail"));
+        job.add_finally(record("cleanup"));
+
+        let dir = std::env::temp_dir().join(format!("workflowo-resume-{}", std::process::id()));
+        let file = Path::new("/project/workflowo.yaml");
+        let run = |selection| {
+            let mut context = RunContext {
+                selection,
+                ..Default::default()
+            };
+            let sink = CheckpointSink::new(
+                CheckpointStore::new(dir.clone()),
+                file.to_path_buf(),
+                vec![CheckpointStore::new(dir.clone()).load(file, "job").unwrap()],
+            );
+            context.events.add_sink(Box::new(sink));
+            assert!(job.run(&mut context).is_err());
+        };
+        run(Selection::All);
+        let completed = CheckpointStore::new(dir.clone())
+            .load(file, "job")
+            .unwrap()
+            .completed;
+        assert_eq!(completed, HashSet::from(["0".to_string()]));
+
+        run(Selection::Resume(completed));
+        assert_eq!(
+            *executed.lock().unwrap(),
+            ["first", "fail", "cleanup", "fail", "cleanup"]
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn selection_test() {
+        let from = Selection::From(vec![1, 2]);
+        assert!(from.skip_reason(&[0]).is_some());
+        assert!(from.skip_reason(&[1]).is_none());
+        assert!(from.skip_reason(&[1, 1, 0]).is_some());
+        assert!(from.skip_reason(&[1, 2, 0]).is_none());
+        assert!(from.skip_reason(&[1, 3]).is_none());
+        assert!(from.skip_reason(&[2]).is_none());
+
+        let only = Selection::Only(vec![1, 2]);
+        assert!(only.skip_reason(&[1]).is_none());
+        assert!(only.skip_reason(&[1, 2, 5]).is_none());
+        assert!(only.skip_reason(&[1, 3]).is_some());
+        assert!(only.skip_reason(&[2]).is_some());
+
+        let resume = Selection::Resume(HashSet::from(["0".to_string(), "1.0".to_string()]));
+        assert!(resume.skip_reason(&[0]).is_some());
+        assert!(resume.skip_reason(&[1]).is_none());
+        assert!(resume.skip_reason(&[1, 0]).is_some());
+    }
+}
@@ -1,21 +1,219 @@
-use super::Task;
+use super::incremental::Incremental;
+use super::{RunContext, Task};
+use crate::events::TaskOutput;
 use anyhow::{bail, Context, Result};
 use std::fmt::{self, Display};
-use std::process::Command;
+use std::io::Read;
+use std::process::{Child, Command, Output, Stdio};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
 
 pub trait ShellCommand {
     fn new(
         args: Vec<String>,
         work_dir: Option<String>,
         allowed_exit_codes: Option<Vec<i32>>,
+        register: Option<String>,
+        incremental: Option<Incremental>,
     ) -> Self;
 }
 
+/// Executes the command and captures its output.
+///
+/// When the run is cancelled the process gets an interrupt, when it is forced it is killed.
+/// On unix the process gets its own process group, so the signals also reach its children.
+fn run_process(command: &mut Command, context: &RunContext) -> Result<Output> {
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(&mut *command, 0);
+
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("could not start the process")?;
+
+    // the pipes are read while waiting, so the process does not block on a full pipe
+    let stdout = read_in_background(child.stdout.take());
+    let stderr = read_in_background(child.stderr.take());
+
+    let mut interrupted = false;
+    let status = loop {
+        if let Some(status) = child.try_wait().context("could not wait for the process")? {
+            break status;
+        }
+        if context.cancel.is_forced() {
+            kill(&mut child);
+            bail!("the process was killed, because the run was cancelled");
+        }
+        if context.cancel.is_cancelled() && !interrupted {
+            interrupt(&mut child);
+            interrupted = true;
+        }
+        thread::sleep(Duration::from_millis(20));
+    };
+    if interrupted {
+        bail!("the process was interrupted, because the run was cancelled");
+    }
+
+    Ok(Output {
+        status,
+        stdout: stdout.join().expect("output reader panicked"),
+        stderr: stderr.join().expect("output reader panicked"),
+    })
+}
+
+/// Sends SIGINT to the process group of the child.
+#[cfg(unix)]
+fn interrupt(child: &mut Child) {
+    // SAFETY: kill has no memory effects, the process group is the one of the child
+    unsafe {
+        libc::kill(-(child.id() as libc::pid_t), libc::SIGINT);
+    }
+}
+
+#[cfg(not(unix))]
+fn interrupt(child: &mut Child) {
+    kill(child);
+}
+
+/// Kills the process group of the child and waits for the child.
+fn kill(child: &mut Child) {
+    #[cfg(unix)]
+    // SAFETY: kill has no memory effects, the process group is the one of the child
+    unsafe {
+        libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL);
+    }
+    // the process could have exited in the meantime
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+fn read_in_background(pipe: Option<impl Read + Send + 'static>) -> JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut buffer = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut buffer);
+        }
+        buffer
+    })
+}
+
+fn describe_shell_command(
+    shell: &str,
+    args: &[String],
+    work_dir: &Option<String>,
+    register: &Option<String>,
+    incremental: &Option<Incremental>,
+) -> String {
+    let mut description = format!("{}: {}", shell, args.join(" "));
+    if let Some(work_dir) = work_dir {
+        description += &format!(" (in {})", work_dir);
+    }
+    if let Some(register) = register {
+        description += &format!(" (registered as {})", register);
+    }
+    if let Some(incremental) = incremental {
+        description += &format!(" {}", incremental.describe());
+    }
+    description
+}
+
+/// Returns the fingerprint of the sources before the command runs, if the task is incremental.
+fn take_fingerprint(incremental: &Option<Incremental>) -> Result<Option<String>> {
+    incremental
+        .as_ref()
+        .map(Incremental::take_fingerprint)
+        .transpose()
+        .context("Could not take the fingerprint of the sources")
+}
+
+/// Executes the prepared command of a shell task, checks its exit code, registers its stdout
+/// and stores the fingerprint of its sources. `task` is the description of the task.
+fn execute_command(
+    mut command: Command,
+    args: &[String],
+    allowed_exit_codes: &Option<Vec<i32>>,
+    register: &Option<String>,
+    incremental: &Option<Incremental>,
+    task: &str,
+    context: &mut RunContext,
+) -> Result<()> {
+    let fingerprint = take_fingerprint(incremental)?;
+    let output = run_process(&mut command, context).context(format!(
+        "Failed while executing {} command",
+        command.get_program().to_string_lossy()
+    ))?;
+    let exit_code = output
+        .status
+        .code()
+        .context("process did not return an exit code")?;
+    context.record_output(TaskOutput {
+        exit_code: Some(exit_code),
+        stdout: Some(String::from_utf8_lossy(&output.stdout).into_owned()),
+        stderr: Some(String::from_utf8_lossy(&output.stderr).into_owned()),
+    });
+    if match allowed_exit_codes {
+        Some(codes) => !codes.contains(&exit_code),
+        None => exit_code != 0,
+    } {
+        bail!(format!(
+            "Error: {:?} did not success and raised an error!\n{}",
+            args,
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+    if let Some(name) = register {
+        context
+            .variables
+            .insert(name.to_owned(), stdout.clone().into());
+    }
+    if let (Some(incremental), Some(fingerprint)) = (incremental, fingerprint) {
+        incremental
+            .store(task, fingerprint, register.as_ref().map(|_| stdout))
+            .context("Could not store the fingerprint of the sources")?;
+    }
+    Ok(())
+}
+
+/// Returns why the task is skipped, if its sources did not change. The stdout registered by the
+/// last run is set again, so later tasks can still use the variable.
+fn incremental_skip_reason(
+    incremental: &Option<Incremental>,
+    register: &Option<String>,
+    task: &str,
+    context: &mut RunContext,
+) -> Option<String> {
+    match incremental {
+        Some(incremental) if !context.force => match incremental.up_to_date(task) {
+            Ok(Some(record)) => {
+                if let (Some(name), Some(stdout)) = (register, record.registered) {
+                    context.variables.insert(name.to_owned(), stdout.into());
+                }
+                Some("sources and outputs are up to date".to_string())
+            }
+            Ok(None) => None,
+            Err(error) => {
+                context.message(&format!(
+                    "Warning: could not check if {} is up to date: {:#}",
+                    task, error
+                ));
+                None
+            }
+        },
+        _ => None,
+    }
+}
+
 #[derive(Debug)]
 pub struct Bash {
     args: Vec<String>,
     work_dir: Option<String>,
     allowed_exit_codes: Option<Vec<i32>>,
+    /// name of the variable the stdout gets registered as
+    register: Option<String>,
+    incremental: Option<Incremental>,
 }
 
 impl ShellCommand for Bash {
@@ -23,43 +221,52 @@ impl ShellCommand for Bash {
         args: Vec<String>,
         work_dir: Option<String>,
         allowed_exit_codes: Option<Vec<i32>>,
+        register: Option<String>,
+        incremental: Option<Incremental>,
     ) -> Self {
         Bash {
             args,
             work_dir,
             allowed_exit_codes,
+            register,
+            incremental,
         }
     }
 }
 
 impl Task for Bash {
-    fn execute(&self) -> Result<()> {
+    #[allow(clippy::needless_borrows_for_generic_args)]
+    fn execute(&self, context: &mut RunContext) -> Result<()> {
         let mut command = Command::new("bash");
 
         if let Some(work_dir) = &self.work_dir {
             command.current_dir(work_dir);
         }
 
-        let output = command
-            .arg("-c")
-            .arg(&self.args.join(" "))
-            .output()
-            .context("Failed while executing bash command")?;
-        let exit_code = output
-            .status
-            .code()
-            .context("process did not return an exit code")?;
-        if match &self.allowed_exit_codes {
-            Some(codes) => !codes.contains(&exit_code),
-            None => exit_code != 0,
-        } {
-            bail!(format!(
-                "Error: {:?} did not success and raised an error!\n{}",
-                &self.args,
-                String::from_utf8_lossy(&output.stderr)
-            ));
-        }
-        Ok(())
+        command.arg("-c").arg(&self.args.join(" "));
+        execute_command(
+            command,
+            &self.args,
+            &self.allowed_exit_codes,
+            &self.register,
+            &self.incremental,
+            &self.describe(),
+            context,
+        )
+    }
+
+    fn describe(&self) -> String {
+        describe_shell_command(
+            "bash",
+            &self.args,
+            &self.work_dir,
+            &self.register,
+            &self.incremental,
+        )
+    }
+
+    fn skip_reason(&self, context: &mut RunContext) -> Option<String> {
+        incremental_skip_reason(&self.incremental, &self.register, &self.describe(), context)
     }
 }
 
@@ -74,6 +281,9 @@ pub struct Cmd {
     args: Vec<String>,
     work_dir: Option<String>,
     allowed_exit_codes: Option<Vec<i32>>,
+    /// name of the variable the stdout gets registered as
+    register: Option<String>,
+    incremental: Option<Incremental>,
 }
 
 impl ShellCommand for Cmd {
@@ -81,43 +291,51 @@ impl ShellCommand for Cmd {
         args: Vec<String>,
         work_dir: Option<String>,
         allowed_exit_codes: Option<Vec<i32>>,
+        register: Option<String>,
+        incremental: Option<Incremental>,
     ) -> Self {
         Cmd {
             args,
             work_dir,
             allowed_exit_codes,
+            register,
+            incremental,
         }
     }
 }
 
 impl Task for Cmd {
-    fn execute(&self) -> Result<()> {
+    fn execute(&self, context: &mut RunContext) -> Result<()> {
         let mut command = Command::new("cmd");
 
         if let Some(work_dir) = &self.work_dir {
             command.current_dir(work_dir);
         }
 
-        let output = command
-            .arg("/c")
-            .args(&self.args)
-            .output()
-            .context("Failed while cmd execution")?;
-        let exit_code = output
-            .status
-            .code()
-            .context("process did not return an exit code")?;
-        if match &self.allowed_exit_codes {
-            Some(codes) => !codes.contains(&exit_code),
-            None => exit_code != 0,
-        } {
-            bail!(format!(
-                "Error: {:?} did not success and raised an error!\n{}",
-                &self.args,
-                String::from_utf8_lossy(&output.stderr)
-            ));
-        }
-        Ok(())
+        command.arg("/c").args(&self.args);
+        execute_command(
+            command,
+            &self.args,
+            &self.allowed_exit_codes,
+            &self.register,
+            &self.incremental,
+            &self.describe(),
+            context,
+        )
+    }
+
+    fn describe(&self) -> String {
+        describe_shell_command(
+            "cmd",
+            &self.args,
+            &self.work_dir,
+            &self.register,
+            &self.incremental,
+        )
+    }
+
+    fn skip_reason(&self, context: &mut RunContext) -> Option<String> {
+        incremental_skip_reason(&self.incremental, &self.register, &self.describe(), context)
     }
 }
 
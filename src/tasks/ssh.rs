@@ -1,4 +1,6 @@
-use super::Task;
+use super::{CancelToken, RunContext, Task};
+use crate::events::TaskOutput;
+use crate::mask;
 use anyhow::{bail, Context, Result};
 use resolve_path::PathResolveExt;
 use std::{
@@ -8,7 +10,7 @@ use std::{
     path::{Path, PathBuf},
 };
 
-fn connect_ssh(addr: &str, username: &str, password: &str) -> Result<ssh2::Session> {
+pub(super) fn connect_ssh(addr: &str, username: &str, password: &str) -> Result<ssh2::Session> {
     // create connection with handshake etc.
     let tcp =
         std::net::TcpStream::connect(addr.to_string() + ":22").context("Connecting failed")?;
@@ -38,15 +40,22 @@ impl SshCommand {
         }
     }
 
-    fn execute(&self, session: &ssh2::Session) -> Result<()> {
-        let (_stdout, exit_code) = execute_on_session(session, &self.command)?;
+    /// Executes the command and appends its output to `stdout`. Returns the exit code.
+    fn execute(
+        &self,
+        session: &ssh2::Session,
+        stdout: &mut String,
+        interrupt: bool,
+        cancel: &CancelToken,
+    ) -> Result<i32> {
+        let exit_code = execute_on_session(session, &self.command, stdout, interrupt, cancel)?;
         if !self.allowed_exit_codes.contains(&exit_code) {
             bail!(format!(
                 "Something went wrong while executing an command (`{}`). Exit code {}.",
                 self.command, exit_code
             ));
         }
-        Ok(())
+        Ok(exit_code)
     }
 }
 
@@ -57,6 +66,9 @@ pub struct SshTask {
     user: String,
     password: String,
     commands: Vec<SshCommand>,
+    /// send Ctrl-C to the remote command when the run is cancelled instead of only closing the
+    /// channel
+    interrupt: bool,
 }
 
 impl SshTask {
@@ -65,69 +77,178 @@ impl SshTask {
         user: String,
         password: String,
         commands: Vec<SshCommand>,
+        interrupt: bool,
     ) -> Self {
+        mask::register(&password);
         Self {
             address,
             user,
             password,
             commands,
+            interrupt,
         }
     }
 }
 
-/// Executes a command on the [`ssh2::Session`]. Returns a Tuple with the Prompt and exit code.
-fn execute_on_session(session: &ssh2::Session, command: &str) -> Result<(String, i32)> {
+/// Executes a command on the [`ssh2::Session`] and appends its output to `stdout`. Returns the
+/// exit code.
+///
+/// When the run is cancelled the channel is closed. With `interrupt` the command runs in a pty
+/// and gets Ctrl-C first, so it can clean up until the run is forced.
+pub(super) fn execute_on_session(
+    session: &ssh2::Session,
+    command: &str,
+    stdout: &mut String,
+    interrupt: bool,
+    cancel: &CancelToken,
+) -> Result<i32> {
     let mut channel = session
         .channel_session()
         .context("Failed to establish a channel session")?;
 
+    if interrupt {
+        channel
+            .request_pty("dumb", None, None)
+            .context("Failed to request a pty")?;
+    }
     channel
         .exec(command)
         .context("Error while executing command via ssh")?;
 
-    let mut stdout = String::new();
-    channel
-        .read_to_string(&mut stdout)
-        .context("Failed to read output of ssh channel")?;
+    session.set_blocking(false);
+    let result = read_until_eof(&mut channel, stdout, interrupt, cancel);
+    session.set_blocking(true);
+    if let Err(error) = result {
+        let _ = channel.close();
+        return Err(error);
+    }
 
     channel
         .wait_close()
         .context("Error while closing the channel session")?;
 
-    Ok((
-        stdout,
-        channel
-            .exit_status()
-            .context("Failed to read exit status")?,
-    ))
+    channel.exit_status().context("Failed to read exit status")
+}
+
+/// Reads the output of the non-blocking channel until it ends or the run is cancelled.
+fn read_until_eof(
+    channel: &mut ssh2::Channel,
+    stdout: &mut String,
+    interrupt: bool,
+    cancel: &CancelToken,
+) -> Result<()> {
+    let mut output = Vec::new();
+    let mut buffer = [0; 4096];
+    let mut interrupted = false;
+    loop {
+        if cancel.is_forced() || (cancel.is_cancelled() && !interrupt) {
+            bail!("the run was cancelled");
+        }
+        if cancel.is_cancelled() && !interrupted {
+            interrupted = true;
+            // Ctrl-C is turned into SIGINT by the pty
+            let _ = channel.write_all(&[3]);
+        }
+        match channel.read(&mut buffer) {
+            Ok(0) if channel.eof() => break,
+            Ok(0) => std::thread::sleep(std::time::Duration::from_millis(20)),
+            Ok(count) => output.extend_from_slice(&buffer[..count]),
+            Err(error) if error.kind() == std::io::ErrorKind::WouldBlock => {
+                std::thread::sleep(std::time::Duration::from_millis(20))
+            }
+            Err(error) => return Err(error).context("Failed to read output of ssh channel"),
+        }
+    }
+    stdout.push_str(&String::from_utf8_lossy(&output));
+    if interrupted {
+        bail!("interrupted");
+    }
+    Ok(())
 }
 
 impl Task for SshTask {
-    fn execute(&self) -> Result<()> {
+    fn execute(&self, context: &mut RunContext) -> Result<()> {
         let sess = connect_ssh(&self.address.to_string(), &self.user, &self.password)
             .context("failed to connect via ssh")?;
 
         // execute commands
+        let mut stdout = String::new();
+        let mut exit_code = None;
+        let mut result = Ok(());
         for command in &self.commands {
-            command
-                .execute(&sess)
-                .context(format!("failed to execute command via ssh: {:?}", &command))?;
+            match command.execute(&sess, &mut stdout, self.interrupt, &context.cancel) {
+                Ok(code) => exit_code = Some(code),
+                Err(error) => {
+                    result =
+                        Err(error
+                            .context(format!("failed to execute command via ssh: {:?}", &command)));
+                    break;
+                }
+            }
         }
-        Ok(())
+        context.record_output(TaskOutput {
+            exit_code,
+            stdout: Some(stdout),
+            stderr: None,
+        });
+        result
+    }
+
+    fn describe(&self) -> String {
+        let commands: Vec<&str> = self
+            .commands
+            .iter()
+            .map(|command| command.command.as_str())
+            .collect();
+        format!(
+            "ssh {}@{}: {}",
+            self.user,
+            self.address,
+            commands.join("; ")
+        )
     }
 }
 
 impl Display for SshTask {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(
-            f,
-            "{}",
-            format!("{:?}", self)
-                .replace(&self.password, "***Not displayed for security reasons***")
-        )
+        write!(f, "{}", mask::mask(&format!("{:?}", self)))
     }
 }
 
+fn describe_download(
+    kind: &str,
+    user: &str,
+    address: &std::net::Ipv4Addr,
+    remote_path: &Path,
+    local_path: &Path,
+) -> String {
+    format!(
+        "{} {}@{}:{} -> {}",
+        kind,
+        user,
+        address,
+        remote_path.display(),
+        local_path.display()
+    )
+}
+
+fn describe_upload(
+    kind: &str,
+    user: &str,
+    address: &std::net::Ipv4Addr,
+    remote_path: &Path,
+    local_path: &Path,
+) -> String {
+    format!(
+        "{} {} -> {}@{}:{}",
+        kind,
+        local_path.display(),
+        user,
+        address,
+        remote_path.display()
+    )
+}
+
 pub trait RemoteTransfer {
     fn new(
         address: std::net::Ipv4Addr,
@@ -155,6 +276,7 @@ impl RemoteTransfer for ScpFileDownload {
         remote_path: PathBuf,
         local_path: PathBuf,
     ) -> Result<Self> {
+        mask::register(&password);
         Ok(ScpFileDownload {
             address,
             user,
@@ -166,7 +288,7 @@ impl RemoteTransfer for ScpFileDownload {
 }
 
 impl Task for ScpFileDownload {
-    fn execute(&self) -> Result<()> {
+    fn execute(&self, _context: &mut RunContext) -> Result<()> {
         let session = connect_ssh(&self.address.to_string(), &self.user, &self.password)
             .context("Failed to connect via ssh")?;
 
@@ -197,16 +319,21 @@ impl Task for ScpFileDownload {
             .context(format!("Error while reading file {:?}", self.local_path))?;
         Ok(())
     }
+
+    fn describe(&self) -> String {
+        describe_download(
+            "scp-download",
+            &self.user,
+            &self.address,
+            &self.remote_path,
+            &self.local_path,
+        )
+    }
 }
 
 impl Display for ScpFileDownload {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(
-            f,
-            "{}",
-            format!("{:?}", self)
-                .replace(&self.password, "***Not displayed for security reasons***")
-        )
+        write!(f, "{}", mask::mask(&format!("{:?}", self)))
     }
 }
 
@@ -227,6 +354,7 @@ impl RemoteTransfer for ScpFileUpload {
         remote_path: PathBuf,
         local_path: PathBuf,
     ) -> Result<Self> {
+        mask::register(&password);
         Ok(ScpFileUpload {
             address,
             user,
@@ -238,7 +366,7 @@ impl RemoteTransfer for ScpFileUpload {
 }
 
 impl Task for ScpFileUpload {
-    fn execute(&self) -> Result<()> {
+    fn execute(&self, _context: &mut RunContext) -> Result<()> {
         let session = connect_ssh(&self.address.to_string(), &self.user, &self.password)
             .context("Failed to connect via ssh")?;
 
@@ -273,16 +401,21 @@ impl Task for ScpFileUpload {
             .context("Error while waiting for close file")?;
         Ok(())
     }
+
+    fn describe(&self) -> String {
+        describe_upload(
+            "scp-upload",
+            &self.user,
+            &self.address,
+            &self.remote_path,
+            &self.local_path,
+        )
+    }
 }
 
 impl Display for ScpFileUpload {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(
-            f,
-            "{}",
-            format!("{:?}", self)
-                .replace(&self.password, "***Not displayed for security reasons***")
-        )
+        write!(f, "{}", mask::mask(&format!("{:?}", self)))
     }
 }
 
@@ -303,6 +436,7 @@ impl RemoteTransfer for SftpDownload {
         remote_path: PathBuf,
         local_path: PathBuf,
     ) -> Result<Self> {
+        mask::register(&password);
         Ok(Self {
             address,
             user,
@@ -314,7 +448,7 @@ impl RemoteTransfer for SftpDownload {
 }
 
 impl Task for SftpDownload {
-    fn execute(&self) -> Result<()> {
+    fn execute(&self, _context: &mut RunContext) -> Result<()> {
         let session = connect_ssh(&self.address.to_string(), &self.user, &self.password)
             .context("Failed to connect via ssh")?;
 
@@ -369,16 +503,21 @@ impl Task for SftpDownload {
         }
         Ok(())
     }
+
+    fn describe(&self) -> String {
+        describe_download(
+            "sftp-download",
+            &self.user,
+            &self.address,
+            &self.remote_path,
+            &self.local_path,
+        )
+    }
 }
 
 impl Display for SftpDownload {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(
-            f,
-            "{}",
-            format!("{:?}", self)
-                .replace(&self.password, "***Not displayed for security reasons***")
-        )
+        write!(f, "{}", mask::mask(&format!("{:?}", self)))
     }
 }
 
@@ -440,6 +579,7 @@ impl RemoteTransfer for SftpUpload {
         remote_path: PathBuf,
         local_path: PathBuf,
     ) -> Result<Self> {
+        mask::register(&password);
         Ok(Self {
             address,
             user,
@@ -452,17 +592,12 @@ impl RemoteTransfer for SftpUpload {
 
 impl Display for SftpUpload {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(
-            f,
-            "{}",
-            format!("{:?}", self)
-                .replace(&self.password, "***Not displayed for security reasons***")
-        )
+        write!(f, "{}", mask::mask(&format!("{:?}", self)))
     }
 }
 
 impl Task for SftpUpload {
-    fn execute(&self) -> Result<()> {
+    fn execute(&self, _context: &mut RunContext) -> Result<()> {
         // check if local stuff is valid
         if !self.local_path.is_dir() && !self.local_path.is_file() {
             bail!(format!(
@@ -493,6 +628,16 @@ impl Task for SftpUpload {
         }
         Ok(())
     }
+
+    fn describe(&self) -> String {
+        describe_upload(
+            "sftp-upload",
+            &self.user,
+            &self.address,
+            &self.remote_path,
+            &self.local_path,
+        )
+    }
 }
 
 fn upload_sftp_directory(sftp: &ssh2::Sftp, local_path: &Path, remote_path: &Path) -> Result<()> {
@@ -529,7 +674,11 @@ fn upload_sftp_directory(sftp: &ssh2::Sftp, local_path: &Path, remote_path: &Pat
 }
 
 /// uploads a file via the sftp connection -> asserts the paths are valid
-fn upload_sftp_file(sftp: &ssh2::Sftp, local_path: &Path, remote_path: &Path) -> Result<()> {
+pub(super) fn upload_sftp_file(
+    sftp: &ssh2::Sftp,
+    local_path: &Path,
+    remote_path: &Path,
+) -> Result<()> {
     // read local file
     let mut local_file = std::fs::File::open(local_path)
         .context(format!("open local file failed {:?}", local_path))?;
@@ -0,0 +1,196 @@
+use super::ssh::connect_ssh;
+use super::{RunContext, Task};
+use crate::mask;
+use crate::yaml_parser::template;
+use anyhow::{Context, Result};
+use serde_yaml::Value;
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+use std::io::Write;
+use std::net::Ipv4Addr;
+use std::path::{Path, PathBuf};
+
+/// Where a rendered template is written to.
+#[derive(Debug)]
+pub enum Destination {
+    Local(PathBuf),
+    /// a path on a remote computer that is written via sftp
+    Remote {
+        address: Ipv4Addr,
+        user: String,
+        password: String,
+        path: PathBuf,
+    },
+}
+
+/// Renders the `{{ }}` placeholders of a file and writes the result to the destination.
+#[derive(Debug)]
+pub struct TemplateTask {
+    src: PathBuf,
+    dest: Destination,
+    /// the permissions of the written file, e.g. `0o644`
+    mode: Option<u32>,
+    /// the params of the job and the variables of the loops around the task
+    vars: HashMap<String, Value>,
+}
+
+impl TemplateTask {
+    pub fn new(
+        src: PathBuf,
+        dest: Destination,
+        mode: Option<u32>,
+        vars: HashMap<String, Value>,
+    ) -> Self {
+        if let Destination::Remote { password, .. } = &dest {
+            mask::register(password);
+        }
+        Self {
+            src,
+            dest,
+            mode,
+            vars,
+        }
+    }
+
+    /// Renders the source file. Registered variables are used as well.
+    fn render(&self, context: &RunContext) -> Result<String> {
+        let content = std::fs::read_to_string(&self.src)
+            .context(format!("Could not read template {:?}", self.src))?;
+        let mut vars = context.variables.clone();
+        vars.extend(self.vars.clone());
+        let rendered = template::render_str(&content, &vars)
+            .context(format!("Could not render template {:?}", self.src))?;
+        template::value_to_string(&rendered)
+    }
+}
+
+fn write_local(path: &Path, content: &str, mode: Option<u32>) -> Result<()> {
+    std::fs::write(path, content).context(format!("Could not write {:?}", path))?;
+    #[cfg(unix)]
+    if let Some(mode) = mode {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))
+            .context(format!("Could not set the mode of {:?}", path))?;
+    }
+    #[cfg(not(unix))]
+    let _ = mode;
+    Ok(())
+}
+
+fn write_remote(sftp: &ssh2::Sftp, path: &Path, content: &str, mode: Option<u32>) -> Result<()> {
+    let mut remote_file = sftp
+        .open_mode(
+            path,
+            ssh2::OpenFlags::WRITE | ssh2::OpenFlags::CREATE | ssh2::OpenFlags::TRUNCATE,
+            mode.unwrap_or(0o644) as i32,
+            ssh2::OpenType::File,
+        )
+        .context(format!("Could not open remote file {:?}", path))?;
+    remote_file
+        .write_all(content.as_bytes())
+        .context(format!("Error while writing to file {:?}", path))?;
+    // the mode of an existing file is not changed by opening it
+    if let Some(mode) = mode {
+        sftp.setstat(
+            path,
+            ssh2::FileStat {
+                size: None,
+                uid: None,
+                gid: None,
+                perm: Some(mode),
+                atime: None,
+                mtime: None,
+            },
+        )
+        .context(format!("Could not set the mode of {:?}", path))?;
+    }
+    Ok(())
+}
+
+impl Task for TemplateTask {
+    fn execute(&self, context: &mut RunContext) -> Result<()> {
+        let content = self.render(context)?;
+        match &self.dest {
+            Destination::Local(path) => write_local(path, &content, self.mode),
+            Destination::Remote {
+                address,
+                user,
+                password,
+                path,
+            } => {
+                let session = connect_ssh(&address.to_string(), user, password)
+                    .context("Error while connect via ssh")?;
+                let sftp = session.sftp().context("Could not create sftp subsystem")?;
+                write_remote(&sftp, path, &content, self.mode)
+            }
+        }
+    }
+
+    fn describe(&self) -> String {
+        let dest = match &self.dest {
+            Destination::Local(path) => path.display().to_string(),
+            Destination::Remote {
+                address,
+                user,
+                path,
+                ..
+            } => format!("{}@{}:{}", user, address, path.display()),
+        };
+        match self.mode {
+            Some(mode) => format!("template {} -> {} ({:o})", self.src.display(), dest, mode),
+            None => format!("template {} -> {}", self.src.display(), dest),
+        }
+    }
+}
+
+impl Display for TemplateTask {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", mask::mask(&format!("{:?}", self)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Destination, TemplateTask};
+    use crate::tasks::{RunContext, Task};
+    use serde_yaml::Value;
+    use std::collections::HashMap;
+
+    #[test]
+    fn template_test() {
+        let dir = std::env::temp_dir().join(format!("workflowo-template-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("app.conf.tmpl"),
+            "server {{ upper(host) }}:{{ port }};\nuser {{ user }};\nkeep {{ .Other }}\n",
+        )
+        .unwrap();
+        let mut vars = HashMap::new();
+        vars.insert("host".to_string(), Value::String("example.org".to_string()));
+        vars.insert("port".to_string(), Value::Number(8080.into()));
+        let task = TemplateTask::new(
+            dir.join("app.conf.tmpl"),
+            Destination::Local(dir.join("app.conf")),
+            Some(0o600),
+            vars,
+        );
+
+        let mut context = RunContext::default();
+        context
+            .variables
+            .insert("user".to_string(), Value::String("www".to_string()));
+        task.execute(&mut context).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dir.join("app.conf")).unwrap(),
+            "server EXAMPLE.ORG:8080;\nuser www;\nkeep {{ .Other }}\n"
+        );
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let metadata = std::fs::metadata(dir.join("app.conf")).unwrap();
+            assert_eq!(metadata.permissions().mode() & 0o777, 0o600);
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,4 @@
+/// Formats bytes as lowercase hex, e.g. for hashes.
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
@@ -0,0 +1,148 @@
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::mpsc::{Receiver, Sender};
+use std::time::{Duration, SystemTime};
+
+/// How often the files are checked for changes.
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Detects changes of files by comparing their modification times.
+pub struct Watcher {
+    /// glob patterns of the watched files
+    patterns: Vec<String>,
+    /// files that are watched in addition to the patterns
+    files: Vec<PathBuf>,
+    snapshot: HashMap<PathBuf, Option<SystemTime>>,
+}
+
+impl Watcher {
+    pub fn new(patterns: Vec<String>, files: Vec<PathBuf>) -> Result<Self> {
+        // `src/**` only matches directories, but the files in them should be watched
+        let patterns: Vec<String> = patterns
+            .into_iter()
+            .map(|pattern| match pattern.ends_with("**") {
+                true => format!("{}/*", pattern),
+                false => pattern,
+            })
+            .collect();
+        for pattern in &patterns {
+            glob::Pattern::new(pattern).context(format!("{} is not a valid glob", pattern))?;
+        }
+        let mut watcher = Self {
+            patterns,
+            files,
+            snapshot: HashMap::new(),
+        };
+        watcher.snapshot = watcher.take_snapshot();
+        Ok(watcher)
+    }
+
+    fn take_snapshot(&self) -> HashMap<PathBuf, Option<SystemTime>> {
+        let mut paths = self.files.clone();
+        for pattern in &self.patterns {
+            if let Ok(matches) = glob::glob(pattern) {
+                paths.extend(matches.flatten().filter(|path| path.is_file()));
+            }
+        }
+        paths
+            .into_iter()
+            .map(|path| {
+                let modified = std::fs::metadata(&path)
+                    .and_then(|metadata| metadata.modified())
+                    .ok();
+                (path, modified)
+            })
+            .collect()
+    }
+
+    /// Returns the files that were created, changed or removed since the last call.
+    pub fn changed_files(&mut self) -> Vec<PathBuf> {
+        let snapshot = self.take_snapshot();
+        let mut changed: Vec<PathBuf> = snapshot
+            .iter()
+            .filter(|(path, modified)| self.snapshot.get(*path) != Some(modified))
+            .map(|(path, _)| path.clone())
+            .collect();
+        changed.extend(
+            self.snapshot
+                .keys()
+                .filter(|path| !snapshot.contains_key(*path))
+                .cloned(),
+        );
+        self.snapshot = snapshot;
+        changed
+    }
+
+    /// Checks the files until the receiver is dropped. The changed files are sent and
+    /// `on_change` is called, e.g. to cancel the running job.
+    pub fn run(mut self, sender: Sender<Vec<PathBuf>>, on_change: impl Fn()) {
+        loop {
+            std::thread::sleep(POLL_INTERVAL);
+            let changed = self.changed_files();
+            if changed.is_empty() {
+                continue;
+            }
+            on_change();
+            if sender.send(changed).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+/// Waits for the next changes and returns all files that changed until no change happened for
+/// the debounce duration.
+pub fn wait_for_changes(receiver: &Receiver<Vec<PathBuf>>, debounce: Duration) -> Vec<PathBuf> {
+    let mut changed = match receiver.recv() {
+        Ok(changed) => changed,
+        Err(_) => return Vec::new(),
+    };
+    while let Ok(more) = receiver.recv_timeout(debounce) {
+        changed.extend(more);
+    }
+    changed.sort();
+    changed.dedup();
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Watcher;
+
+    #[test]
+    fn changed_files_test() {
+        let dir = std::env::temp_dir().join(format!("workflowo-watch-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.txt"), "a").unwrap();
+
+        let mut watcher = Watcher::new(
+            vec![
+                dir.join("*.txt").to_string_lossy().into_owned(),
+                dir.join("sub").join("**").to_string_lossy().into_owned(),
+            ],
+            vec![dir.join("workflowo.yaml")],
+        )
+        .unwrap();
+        assert!(watcher.changed_files().is_empty());
+
+        std::fs::write(dir.join("b.txt"), "b").unwrap();
+        std::fs::write(dir.join("workflowo.yaml"), "").unwrap();
+        std::fs::write(dir.join("c.log"), "c").unwrap();
+        let mut changed = watcher.changed_files();
+        changed.sort();
+        assert_eq!(changed, [dir.join("b.txt"), dir.join("workflowo.yaml")]);
+
+        std::fs::create_dir_all(dir.join("sub").join("deep")).unwrap();
+        std::fs::write(dir.join("sub").join("deep").join("d.rs"), "d").unwrap();
+        assert_eq!(
+            watcher.changed_files(),
+            [dir.join("sub").join("deep").join("d.rs")]
+        );
+
+        std::fs::remove_file(dir.join("a.txt")).unwrap();
+        assert_eq!(watcher.changed_files(), [dir.join("a.txt")]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
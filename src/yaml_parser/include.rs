@@ -0,0 +1,282 @@
+use super::{get_entry, read_yaml_file, render, RESERVED_KEYS};
+use anyhow::{bail, Context, Result};
+use resolve_path::PathResolveExt;
+use serde_yaml::{Mapping, Value};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// Where a job was defined. Relative paths and the names of other jobs in a job are resolved in
+/// the scope of its file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scope {
+    /// the directory of the file of the job
+    pub base_dir: PathBuf,
+    /// the namespace of the file followed by `:`, empty for the root file and files that are
+    /// included without a namespace
+    pub namespace: String,
+}
+
+/// The root file with the jobs of all included files.
+pub struct Loaded {
+    /// the reserved keys and jobs of the root file and the jobs of the included files
+    pub root_map: Mapping,
+    pub base_dir: PathBuf,
+    /// the scope of every job
+    pub scopes: HashMap<String, Scope>,
+    /// all files that were read, the root file first
+    pub files: Vec<PathBuf>,
+}
+
+/// One entry of `INCLUDE`.
+struct Include {
+    /// a path or glob relative to the including file
+    pattern: String,
+    namespace: Option<String>,
+}
+
+fn parse_includes(value: Value) -> Result<Vec<Include>> {
+    let items = match value {
+        Value::Sequence(items) => items,
+        Value::String(pattern) => vec![Value::String(pattern)],
+        _ => bail!("INCLUDE is not a sequence"),
+    };
+    let mut includes = Vec::new();
+    for item in items {
+        match item {
+            Value::String(pattern) => includes.push(Include {
+                pattern,
+                namespace: None,
+            }),
+            Value::Mapping(map) => {
+                let pattern = match get_entry(&map, "path".into()) {
+                    Some(Value::String(pattern)) => pattern,
+                    Some(_) => bail!("path of include is not a string"),
+                    None => bail!("path of include is not given"),
+                };
+                let namespace = match get_entry(&map, "as".into()) {
+                    Some(Value::String(namespace)) => {
+                        if namespace.is_empty() || namespace.contains(':') {
+                            bail!(format!(
+                                "namespace {:?} of include {} is empty or contains ':'",
+                                namespace, pattern
+                            ));
+                        }
+                        Some(namespace)
+                    }
+                    Some(_) => bail!(format!("as of include {} is not a string", pattern)),
+                    None => None,
+                };
+                includes.push(Include { pattern, namespace });
+            }
+            _ => bail!(format!("include {:?} is not a string or map", item)),
+        }
+    }
+    Ok(includes)
+}
+
+struct Loader {
+    ids: HashMap<String, Value>,
+    root_map: Mapping,
+    scopes: HashMap<String, Scope>,
+    /// the file every job was defined in
+    job_files: HashMap<String, PathBuf>,
+    files: Vec<PathBuf>,
+    /// the files and namespaces that were loaded, a file that is included twice is skipped
+    loaded: HashSet<(PathBuf, String)>,
+    /// the files that are being loaded, to detect cycles
+    stack: Vec<PathBuf>,
+}
+
+impl Loader {
+    /// Reads and renders the file, adds its jobs and loads its includes.
+    fn load(&mut self, path: &Path, namespace: &str) -> Result<()> {
+        let path = path
+            .canonicalize()
+            .context(format!("Could not resolve the path of {:?}", path))?;
+        if self.stack.contains(&path) {
+            let cycle: Vec<String> = self
+                .stack
+                .iter()
+                .skip_while(|file| **file != path)
+                .chain(std::iter::once(&path))
+                .map(|file| file.display().to_string())
+                .collect();
+            bail!(format!("Include cycle: {}", cycle.join(" -> ")));
+        }
+        if !self.loaded.insert((path.clone(), namespace.to_string())) {
+            return Ok(());
+        }
+        let base_dir = path
+            .parent()
+            .context("file has no parent directory")?
+            .to_path_buf();
+
+        let mut value = read_yaml_file(path.clone()).context("reading yaml error")?;
+        render::render(&mut self.ids, &base_dir, &mut value).context("resolving yaml error")?;
+        let map = match value {
+            Value::Mapping(map) => map,
+            _ => bail!("yaml file is not a map"),
+        };
+        if !self.files.contains(&path) {
+            self.files.push(path.clone());
+        }
+        let is_root = self.stack.is_empty();
+
+        for (key, job) in &map {
+            let name = match key.as_str() {
+                Some("INCLUDE") => continue,
+                Some(name) if RESERVED_KEYS.contains(&name) => {
+                    // the settings of included files are ignored
+                    if is_root {
+                        self.root_map.insert(key.clone(), job.clone());
+                    }
+                    continue;
+                }
+                Some(name) => format!("{}{}", namespace, name),
+                None if is_root => {
+                    // reported by the validation
+                    self.root_map.insert(key.clone(), job.clone());
+                    continue;
+                }
+                None => bail!(format!(
+                    "Job {:?} of {:?} has not a valid string as name",
+                    key, path
+                )),
+            };
+            if let Some(other) = self.job_files.get(&name) {
+                bail!(format!(
+                    "Job {} of {:?} is already defined in {:?}",
+                    name, path, other
+                ));
+            }
+            self.root_map.insert(name.clone().into(), job.clone());
+            self.job_files.insert(name.clone(), path.clone());
+            self.scopes.insert(
+                name,
+                Scope {
+                    base_dir: base_dir.clone(),
+                    namespace: namespace.to_string(),
+                },
+            );
+        }
+
+        let includes = match get_entry(&map, "INCLUDE".into()) {
+            Some(value) => {
+                parse_includes(value).context(format!("INCLUDE of {:?} is invalid", path))?
+            }
+            None => return Ok(()),
+        };
+        self.stack.push(path.clone());
+        for include in includes {
+            let pattern = Path::new(&include.pattern)
+                .try_resolve_in(&base_dir)
+                .context(format!("could not resolve include {}", include.pattern))?
+                .to_string_lossy()
+                .into_owned();
+            let mut files = Vec::new();
+            for file in glob::glob(&pattern).context(format!("{} is not a valid glob", pattern))? {
+                files.push(file.context(format!("could not read path of glob {}", pattern))?);
+            }
+            if files.is_empty() {
+                bail!(format!(
+                    "Include {} of {:?} does not match any file",
+                    include.pattern, path
+                ));
+            }
+            files.sort();
+            let namespace = match &include.namespace {
+                Some(name) => format!("{}{}:", namespace, name),
+                None => namespace.to_string(),
+            };
+            for file in files {
+                self.load(&file, &namespace)
+                    .context(format!("Could not include {:?} from {:?}", file, path))?;
+            }
+        }
+        self.stack.pop();
+        Ok(())
+    }
+}
+
+/// Reads the workflow file and all files it includes.
+pub fn load(path: &Path) -> Result<Loaded> {
+    let mut loader = Loader {
+        ids: HashMap::new(),
+        root_map: Mapping::new(),
+        scopes: HashMap::new(),
+        job_files: HashMap::new(),
+        files: Vec::new(),
+        loaded: HashSet::new(),
+        stack: Vec::new(),
+    };
+    loader.load(path, "")?;
+    let base_dir = loader.files[0]
+        .parent()
+        .context("file has no parent directory")?
+        .to_path_buf();
+    Ok(Loaded {
+        root_map: loader.root_map,
+        base_dir,
+        scopes: loader.scopes,
+        files: loader.files,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::load;
+    use std::path::Path;
+
+    fn write(dir: &Path, name: &str, content: &str) {
+        let path = dir.join(name);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn include_test() {
+        let dir = std::env::temp_dir().join(format!("workflowo-include-{}", std::process::id()));
+        write(
+            &dir,
+            "workflowo.yaml",
+            "INCLUDE:\n  - jobs/*.yaml\n  - {path: lib/lib.yaml, as: lib}\nbuild:\n  - lib:build\n",
+        );
+        write(&dir, "jobs/test.yaml", "test:\n  - print: test\n");
+        write(
+            &dir,
+            "lib/lib.yaml",
+            "INCLUDE: [{path: util.yaml, as: util}]\nLOG: logs\nbuild:\n  - util:clean\n",
+        );
+        write(&dir, "lib/util.yaml", "clean:\n  - print: clean\n");
+
+        let loaded = load(&dir.join("workflowo.yaml")).unwrap();
+        let names: Vec<&str> = loaded
+            .root_map
+            .keys()
+            .filter_map(|key| key.as_str())
+            .collect();
+        assert_eq!(names, ["build", "test", "lib:build", "lib:util:clean"]);
+        let lib_dir = dir.join("lib").canonicalize().unwrap();
+        assert_eq!(loaded.scopes["lib:build"].base_dir, lib_dir);
+        assert_eq!(loaded.scopes["lib:util:clean"].namespace, "lib:util:");
+        assert_eq!(loaded.scopes["build"].namespace, "");
+        assert_eq!(loaded.files.len(), 4);
+
+        // collisions
+        write(&dir, "jobs/other.yaml", "test:\n  - print: other\n");
+        let error = format!("{:#}", load(&dir.join("workflowo.yaml")).err().unwrap());
+        assert!(error.contains("Job test of"), "{}", error);
+        std::fs::remove_file(dir.join("jobs/other.yaml")).unwrap();
+
+        // cycles
+        write(
+            &dir,
+            "lib/util.yaml",
+            "INCLUDE: [lib.yaml]\nclean:\n  - print: clean\n",
+        );
+        let error = format!("{:#}", load(&dir.join("workflowo.yaml")).err().unwrap());
+        assert!(error.contains("Include cycle"), "{}", error);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,15 +1,31 @@
+use crate::logs::LogConfig;
+use crate::tasks::approve::{ApproveTask, OnTimeout};
+use crate::tasks::archive::{ArchiveTask, ExtractTask, Filter, Format};
+use crate::tasks::files::{
+    ChmodTask, CopyTask, MkdirTask, MoveTask, Overwrite, RemoveTask, SymlinkTask, WriteFileTask,
+};
+use crate::tasks::incremental::Incremental;
+use crate::tasks::loops::{ForEach, Items, Matrix, TaskFactory};
 use crate::tasks::shell::{Bash, Cmd, ShellCommand};
 use crate::tasks::ssh::{
     RemoteTransfer, ScpFileDownload, ScpFileUpload, SftpDownload, SftpUpload, SshCommand, SshTask,
 };
+use crate::tasks::template::{Destination, TemplateTask};
 use crate::tasks::{Job, OSDependent, PrintTask, Task, OS};
 use anyhow::{bail, Context, Result};
+use resolve_path::PathResolveExt;
 use serde_yaml::{self, Mapping, Value};
+use std::collections::HashMap;
 use std::fs::File;
 use std::net::Ipv4Addr;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+mod include;
+mod params;
 mod render;
+pub(crate) mod template;
 
 /// Gets an entry out of a map.
 fn get_entry(map: &Mapping, key: Value) -> Option<Value> {
@@ -26,67 +42,111 @@ fn read_yaml_file(path: PathBuf) -> Result<Value> {
     Ok(value)
 }
 
-fn parse_jobs(data: Mapping) -> Vec<Job> {
-    let mut jobs = Vec::new();
-
-    for (root_key, _root_value) in &data {
-        if !root_key.is_string() {
-            panic!("Job {:?} is has not a valid string as name", root_key);
-        }
-
-        if root_key.as_str().unwrap() == "IGNORE" {
-            continue;
-        }
-
-        let job = parse_job(&data, root_key.as_str().unwrap().to_string());
-
-        match job {
-            Ok(value) => jobs.push(value),
-            Err(error) => panic!("Error: {}", error),
-        }
-    }
-
-    jobs
-}
-
-fn parse_job(root_map: &Mapping, name: String) -> Result<Job> {
-    let job_entry = match get_entry(root_map, name.clone().into()) {
-        Some(value) => value,
+fn parse_job(workflow: &Workflow, name: String, args: &HashMap<String, Value>) -> Result<Job> {
+    let (name, job_entry) = match workflow.find_job(&name) {
+        Some(job) => job,
         _ => bail!("Job not found"),
     };
+    let workflow = &workflow.scoped(&name);
 
-    let job_sequence = match job_entry.as_sequence() {
-        Some(value) => value,
-        None => {
+    // a job is either a sequence of tasks or a map with params and tasks
+    let (params, mut job_sequence, mut finally_sequence) = match job_entry {
+        Value::Sequence(seq) => (Vec::new(), seq, Vec::new()),
+        Value::Mapping(job_map) => {
+            let params = match get_entry(&job_map, "params".into()) {
+                Some(params_value) => params::parse_params(&params_value)
+                    .context(format!("Error in params of job {}", name))?,
+                None => Vec::new(),
+            };
+            let tasks = match get_entry(&job_map, "tasks".into()) {
+                Some(Value::Sequence(seq)) => seq,
+                Some(_) => bail!(format!("tasks of {} is not a sequence", name)),
+                None => bail!(format!("Job {} has no tasks", name)),
+            };
+            let finally = match get_entry(&job_map, "finally".into()) {
+                Some(Value::Sequence(seq)) => seq,
+                Some(_) => bail!(format!("finally of {} is not a sequence", name)),
+                None => Vec::new(),
+            };
+            (params, tasks, finally)
+        }
+        _ => {
             bail!(format!("Child of {} is not a sequence", name));
         }
     };
 
+    let vars = params::resolve_args(&params, args)
+        .context(format!("Invalid arguments for job {}", name))?;
+    let workflow = &Workflow {
+        vars: vars.clone(),
+        ..workflow.clone()
+    };
+    for child in job_sequence.iter_mut().chain(finally_sequence.iter_mut()) {
+        template::substitute(child, &vars).context(format!(
+            "Error while inserting parameters into job {}",
+            name
+        ))?;
+    }
+
     let mut job = Job::new(name.clone());
 
-    for child in job_sequence {
-        match parse_task(root_map, child) {
-            Ok(task) => job.add_child(task),
+    // approvals show the tasks that follow them, so they are created after all other tasks
+    let mut children: Vec<Option<Box<dyn Task>>> = Vec::new();
+    let mut approvals = Vec::new();
+    for (index, child) in job_sequence.iter().enumerate() {
+        let result = match get_approve(child) {
+            Some(approve) => parse_approve(approve).map(|approve| {
+                approvals.push((index, approve));
+                None
+            }),
+            None => parse_task(workflow, child).map(Some),
+        };
+        match result {
+            Ok(task) => children.push(task),
             Err(error) => {
-                bail!(format!("Error while parsing job {}: {}", name, error));
+                return Err(error.context(format!("Error while parsing job {}", name)));
+            }
+        }
+    }
+    for (index, mut approve) in approvals {
+        approve.set_summary(
+            children[index + 1..]
+                .iter()
+                .flatten()
+                .map(|task| task.describe())
+                .collect(),
+        );
+        children[index] = Some(Box::new(approve));
+    }
+    for child in children.into_iter().flatten() {
+        job.add_child(child);
+    }
+    for task in &finally_sequence {
+        match parse_task(workflow, task) {
+            Ok(task) => job.add_finally(task),
+            Err(error) => {
+                return Err(error.context(format!("Error while parsing finally of job {}", name)));
             }
         }
     }
     Ok(job)
 }
 
-fn parse_task(root_map: &Mapping, value: &Value) -> Result<Box<dyn Task>> {
+fn parse_task(workflow: &Workflow, value: &Value) -> Result<Box<dyn Task>> {
     if value.is_string() {
-        match parse_job(root_map, value.as_str().unwrap().to_string()) {
+        match parse_job(
+            workflow,
+            value.as_str().unwrap().to_string(),
+            &HashMap::new(),
+        ) {
             Ok(child_job) => {
                 return Ok(Box::new(child_job));
             }
             Err(error) => {
-                bail!(format!(
-                    "parsing error for task {}: {}",
-                    value.as_str().unwrap(),
-                    error
-                ))
+                return Err(error.context(format!(
+                    "parsing error for task {}",
+                    value.as_str().unwrap()
+                )))
             }
         }
     }
@@ -101,64 +161,156 @@ fn parse_task(root_map: &Mapping, value: &Value) -> Result<Box<dyn Task>> {
         }
 
         match task_key.as_str().unwrap() {
-            "bash" => match parse_shell_command_task::<Bash>(task_value) {
+            "bash" => match parse_shell_command_task::<Bash>(workflow, task_value) {
                 Ok(task) => return Ok(Box::new(task)),
                 Err(err) => {
-                    bail!(format!("Error with bash task: {}", err));
+                    return Err(err.context("Error with bash task"));
                 }
             },
-            "cmd" => match parse_shell_command_task::<Cmd>(task_value) {
+            "cmd" => match parse_shell_command_task::<Cmd>(workflow, task_value) {
                 Ok(task) => return Ok(Box::new(task)),
                 Err(err) => {
-                    bail!(format!("Error with cmd task: {}", err));
+                    return Err(err.context("Error with cmd task"));
                 }
             },
-            "on-windows" => match parse_os_dependent(root_map, OS::Windows, task_value) {
+            "on-windows" => match parse_os_dependent(workflow, OS::Windows, task_value) {
                 Ok(task) => return Ok(Box::new(task)),
                 Err(error) => {
-                    bail!(format!("parsing Error in on-windows: {}", error));
+                    return Err(error.context("parsing Error in on-windows"));
                 }
             },
-            "on-linux" => match parse_os_dependent(root_map, OS::Linux, task_value) {
+            "on-linux" => match parse_os_dependent(workflow, OS::Linux, task_value) {
                 Ok(task) => return Ok(Box::new(task)),
                 Err(error) => {
-                    bail!(format!("parsing Error in on-windows: {}", error));
+                    return Err(error.context("parsing Error in on-windows"));
                 }
             },
             "ssh" => match parse_ssh(task_value) {
                 Ok(task) => return Ok(Box::new(task)),
                 Err(error) => {
-                    bail!(format!("Parsing Error in ssh: {}", error));
+                    return Err(error.context("Parsing Error in ssh"));
+                }
+            },
+            "scp-download" => {
+                match parse_remote_transfer::<ScpFileDownload>(workflow, task_value) {
+                    Ok(task) => return Ok(Box::new(task)),
+                    Err(error) => {
+                        return Err(error.context("Parsing Error in scp-download"));
+                    }
+                }
+            }
+            "scp-upload" => match parse_remote_transfer::<ScpFileUpload>(workflow, task_value) {
+                Ok(task) => return Ok(Box::new(task)),
+                Err(error) => {
+                    return Err(error.context("Parsing Error in scp-upload"));
+                }
+            },
+            "sftp-download" => match parse_remote_transfer::<SftpDownload>(workflow, task_value) {
+                Ok(task) => return Ok(Box::new(task)),
+                Err(error) => {
+                    return Err(error.context("Parsing Error in sftp-download"));
+                }
+            },
+            "sftp-upload" => match parse_remote_transfer::<SftpUpload>(workflow, task_value) {
+                Ok(task) => return Ok(Box::new(task)),
+                Err(error) => {
+                    return Err(error.context("Parsing Error in sftp-upload"));
+                }
+            },
+            "call" => match parse_call(workflow, task_value) {
+                Ok(task) => return Ok(Box::new(task)),
+                Err(error) => {
+                    return Err(error.context("Parsing Error in call"));
                 }
             },
-            "scp-download" => match parse_remote_transfer::<ScpFileDownload>(task_value) {
+            "foreach" => match parse_foreach(workflow, task_value) {
                 Ok(task) => return Ok(Box::new(task)),
                 Err(error) => {
-                    bail!(format!("Parsing Error in scp-download: {}", error));
+                    return Err(error.context("Parsing Error in foreach"));
                 }
             },
-            "scp-upload" => match parse_remote_transfer::<ScpFileUpload>(task_value) {
+            "matrix" => match parse_matrix(workflow, task_value) {
                 Ok(task) => return Ok(Box::new(task)),
                 Err(error) => {
-                    bail!(format!("Parsing Error in scp-upload: {}", error));
+                    return Err(error.context("Parsing Error in matrix"));
                 }
             },
-            "sftp-download" => match parse_remote_transfer::<SftpDownload>(task_value) {
+            "approve" => match parse_approve(task_value) {
                 Ok(task) => return Ok(Box::new(task)),
                 Err(error) => {
-                    bail!(format!("Parsing Error in sftp-download: {}", error));
+                    return Err(error.context("Parsing Error in approve"));
                 }
             },
-            "sftp-upload" => match parse_remote_transfer::<SftpUpload>(task_value) {
+            "copy" => match parse_copy(workflow, task_value) {
                 Ok(task) => return Ok(Box::new(task)),
                 Err(error) => {
-                    bail!(format!("Parsing Error in sftp-upload: {}", error));
+                    return Err(error.context("Parsing Error in copy"));
+                }
+            },
+            "move" => match parse_move(workflow, task_value) {
+                Ok(task) => return Ok(Box::new(task)),
+                Err(error) => {
+                    return Err(error.context("Parsing Error in move"));
+                }
+            },
+            "remove" => match parse_remove(workflow, task_value) {
+                Ok(task) => return Ok(Box::new(task)),
+                Err(error) => {
+                    return Err(error.context("Parsing Error in remove"));
+                }
+            },
+            "mkdir" => match parse_mkdir(workflow, task_value) {
+                Ok(task) => return Ok(Box::new(task)),
+                Err(error) => {
+                    return Err(error.context("Parsing Error in mkdir"));
+                }
+            },
+            "write-file" => match parse_write_file(workflow, task_value) {
+                Ok(task) => return Ok(Box::new(task)),
+                Err(error) => {
+                    return Err(error.context("Parsing Error in write-file"));
+                }
+            },
+            "append-file" => match parse_append_file(workflow, task_value) {
+                Ok(task) => return Ok(Box::new(task)),
+                Err(error) => {
+                    return Err(error.context("Parsing Error in append-file"));
+                }
+            },
+            "chmod" => match parse_chmod(workflow, task_value) {
+                Ok(task) => return Ok(Box::new(task)),
+                Err(error) => {
+                    return Err(error.context("Parsing Error in chmod"));
+                }
+            },
+            "symlink" => match parse_symlink(workflow, task_value) {
+                Ok(task) => return Ok(Box::new(task)),
+                Err(error) => {
+                    return Err(error.context("Parsing Error in symlink"));
+                }
+            },
+            "archive" => match parse_archive(workflow, task_value) {
+                Ok(task) => return Ok(Box::new(task)),
+                Err(error) => {
+                    return Err(error.context("Parsing Error in archive"));
+                }
+            },
+            "extract" => match parse_extract(workflow, task_value) {
+                Ok(task) => return Ok(Box::new(task)),
+                Err(error) => {
+                    return Err(error.context("Parsing Error in extract"));
+                }
+            },
+            "template" => match parse_template(workflow, task_value) {
+                Ok(task) => return Ok(Box::new(task)),
+                Err(error) => {
+                    return Err(error.context("Parsing Error in template"));
                 }
             },
             "print" => match parse_print(task_value) {
                 Ok(task) => return Ok(Box::new(task)),
                 Err(error) => {
-                    bail!(format!("Parsing Error in print: {}", error));
+                    return Err(error.context("Parsing Error in print"));
                 }
             },
             _ => bail!("unrecognized task in"),
@@ -168,6 +320,229 @@ fn parse_task(root_map: &Mapping, value: &Value) -> Result<Box<dyn Task>> {
     bail!("Task could not be parsed");
 }
 
+fn parse_call(workflow: &Workflow, value: &Value) -> Result<Job> {
+    match value {
+        // case it is just the job name `call: some_job`
+        Value::String(job_name) => parse_job(workflow, job_name.to_owned(), &HashMap::new()),
+        Value::Mapping(call_map) => {
+            let job_name = match get_entry(call_map, "job".into()) {
+                Some(Value::String(job_name)) => job_name,
+                Some(_) => bail!("job is not a string"),
+                None => bail!("job is not given"),
+            };
+
+            let mut args = HashMap::new();
+            match get_entry(call_map, "with".into()) {
+                Some(Value::Mapping(with_map)) => {
+                    for (key, arg) in with_map {
+                        match key {
+                            Value::String(arg_name) => args.insert(arg_name, arg),
+                            _ => bail!(format!("argument name {:?} is not a string", key)),
+                        };
+                    }
+                }
+                Some(_) => bail!("with is not a map"),
+                None => {}
+            }
+
+            parse_job(workflow, job_name.clone(), &args)
+                .context(format!("Could not call job {}", job_name))
+        }
+        _ => bail!("call is neither a job name nor a map"),
+    }
+}
+
+/// Creates a factory that parses task definitions after inserting the variables.
+fn task_factory(workflow: &Workflow) -> TaskFactory {
+    let workflow = workflow.clone();
+    Arc::new(move |definition, variables| {
+        let mut definition = definition.to_owned();
+        template::substitute(&mut definition, variables)
+            .context("Error while inserting variables")?;
+        let mut workflow = workflow.clone();
+        workflow.vars.extend(variables.clone());
+        parse_task(&workflow, &definition)
+    })
+}
+
+/// Gets the `tasks` sequence of a loop.
+fn parse_loop_tasks(map: &Mapping) -> Result<Vec<Value>> {
+    match get_entry(map, "tasks".into()) {
+        Some(Value::Sequence(tasks)) => Ok(tasks),
+        Some(_) => bail!("tasks is not a sequence"),
+        None => bail!("tasks are not given"),
+    }
+}
+
+/// Parses the tasks of a loop with the first value of each of its variables, so invalid tasks
+/// are reported before the workflow runs.
+///
+/// Variables without a value are only known while running (e.g. the items of `glob` and
+/// `lines`), the tasks that use them are checked while running.
+fn check_loop_tasks(
+    factory: &TaskFactory,
+    tasks: &[Value],
+    variables: HashMap<String, Option<Value>>,
+) -> Result<()> {
+    let unknown: HashMap<String, Value> = variables
+        .iter()
+        .filter(|(_, value)| value.is_none())
+        .map(|(name, _)| (name.clone(), Value::Null))
+        .collect();
+    let known: HashMap<String, Value> = variables
+        .into_iter()
+        .filter_map(|(name, value)| Some((name, value?)))
+        .collect();
+    for (index, task) in tasks.iter().enumerate() {
+        // a task uses an unknown variable if inserting it changes the task
+        let mut inserted = task.clone();
+        template::substitute(&mut inserted, &unknown)?;
+        if inserted != *task {
+            continue;
+        }
+        factory(task, &known).context(format!("Task {}(first is 0) of loop is invalid", index))?;
+    }
+    Ok(())
+}
+
+fn parse_foreach(workflow: &Workflow, value: &Value) -> Result<ForEach> {
+    let map = match value {
+        Value::Mapping(map) => map,
+        _ => bail!("Value is not of type Mapping"),
+    };
+
+    let items = match (
+        get_entry(map, "items".into()),
+        get_entry(map, "glob".into()),
+        get_entry(map, "lines".into()),
+    ) {
+        (Some(Value::Sequence(items)), None, None) => Items::List(items),
+        (Some(_), None, None) => bail!("items is not a sequence"),
+        (None, Some(Value::String(pattern)), None) => Items::Glob(
+            workflow
+                .resolve_path(Path::new(&pattern))
+                .context(format!("could not resolve glob {}", pattern))?
+                .to_string_lossy()
+                .into_owned(),
+        ),
+        (None, Some(_), None) => bail!("glob is not a string"),
+        (None, None, Some(Value::String(variable))) => Items::Lines(variable),
+        (None, None, Some(_)) => bail!("lines is not a string"),
+        _ => bail!("exactly one of items, glob or lines has to be given"),
+    };
+
+    let variable = match get_entry(map, "as".into()) {
+        Some(Value::String(variable)) => variable,
+        Some(_) => bail!("as is not a string"),
+        None => "item".to_string(),
+    };
+
+    let tasks = parse_loop_tasks(map)?;
+    let factory = task_factory(workflow);
+    let first = match &items {
+        Items::List(items) => items.first().cloned(),
+        _ => None,
+    };
+    check_loop_tasks(&factory, &tasks, HashMap::from([(variable.clone(), first)]))?;
+    Ok(ForEach::new(items, variable, tasks, factory))
+}
+
+fn parse_matrix(workflow: &Workflow, value: &Value) -> Result<Matrix> {
+    let map = match value {
+        Value::Mapping(map) => map,
+        _ => bail!("Value is not of type Mapping"),
+    };
+
+    let axes = match get_entry(map, "axes".into()) {
+        Some(Value::Mapping(axes_map)) => {
+            let mut axes = Vec::new();
+            for (name, values) in axes_map {
+                let name = match name {
+                    Value::String(name) => name,
+                    _ => bail!(format!("axis name {:?} is not a string", name)),
+                };
+                match values {
+                    Value::Sequence(values) => axes.push((name, values)),
+                    _ => bail!(format!("values of axis {} are not a sequence", name)),
+                }
+            }
+            axes
+        }
+        Some(_) => bail!("axes is not a map"),
+        None => bail!("axes are not given"),
+    };
+
+    // `true` runs as many combinations at the same time as there are cpus
+    let parallel = match get_entry(map, "parallel".into()) {
+        Some(Value::Bool(true)) => std::thread::available_parallelism()
+            .map(|cpus| cpus.get())
+            .unwrap_or(1),
+        Some(Value::Bool(false)) | None => 1,
+        Some(Value::Number(number)) => match number.as_u64() {
+            Some(parallel) if parallel >= 1 => parallel as usize,
+            _ => bail!(format!("parallel {} is not a positive number", number)),
+        },
+        Some(_) => bail!("parallel is not a bool or a number"),
+    };
+
+    let tasks = parse_loop_tasks(map)?;
+    let factory = task_factory(workflow);
+    check_loop_tasks(
+        &factory,
+        &tasks,
+        axes.iter()
+            .map(|(name, values)| (name.clone(), values.first().cloned()))
+            .collect(),
+    )?;
+    Ok(Matrix::new(axes, parallel, tasks, factory))
+}
+
+/// Returns the value of the task if it is an `approve` task.
+fn get_approve(value: &Value) -> Option<&Value> {
+    match value.as_mapping()?.iter().next()? {
+        (Value::String(key), approve) if key == "approve" => Some(approve),
+        _ => None,
+    }
+}
+
+fn parse_approve(value: &Value) -> Result<ApproveTask> {
+    let map = match value {
+        Value::String(message) => {
+            return Ok(ApproveTask::new(message.clone(), None, OnTimeout::Abort))
+        }
+        Value::Mapping(map) => map,
+        _ => bail!("approve is neither a message nor a map"),
+    };
+    let message = match get_entry(map, "message".into()) {
+        Some(Value::String(message)) => message,
+        Some(_) => bail!("message is not a string"),
+        None => bail!("message is not given"),
+    };
+    let timeout = match get_entry(map, "timeout".into()) {
+        Some(Value::Number(seconds)) => Some(Duration::from_secs_f64(
+            seconds
+                .as_f64()
+                .filter(|seconds| *seconds > 0.0)
+                .context("timeout is not a positive number of seconds")?,
+        )),
+        Some(_) => bail!("timeout is not a positive number of seconds"),
+        None => None,
+    };
+    let on_timeout = match get_entry(map, "on_timeout".into()) {
+        Some(Value::String(action)) => match action.as_str() {
+            "abort" => OnTimeout::Abort,
+            "approve" => OnTimeout::Approve,
+            _ => bail!(format!(
+                "on_timeout {} is neither abort nor approve",
+                action
+            )),
+        },
+        Some(_) => bail!("on_timeout is not a string"),
+        None => OnTimeout::Abort,
+    };
+    Ok(ApproveTask::new(message, timeout, on_timeout))
+}
+
 fn parse_print(value: &Value) -> Result<PrintTask> {
     match value {
         Value::String(prompt) => Ok(PrintTask::new(prompt.to_string())),
@@ -175,7 +550,8 @@ fn parse_print(value: &Value) -> Result<PrintTask> {
     }
 }
 
-fn parse_remote_transfer<T: RemoteTransfer>(value: &Value) -> Result<T> {
+#[allow(clippy::needless_question_mark)]
+fn parse_remote_transfer<T: RemoteTransfer>(workflow: &Workflow, value: &Value) -> Result<T> {
     if !value.is_mapping() {
         bail!("Value is not of type Mapping");
     }
@@ -220,10 +596,9 @@ fn parse_remote_transfer<T: RemoteTransfer>(value: &Value) -> Result<T> {
 
     let local_path = match get_entry(value.as_mapping().unwrap(), "local_path".into()) {
         Some(value) => match value {
-            Value::String(string) => match std::path::PathBuf::from_str(&string) {
-                Ok(value) => value,
-                Err(error) => bail!(error.to_string()),
-            },
+            Value::String(string) => workflow
+                .resolve_path(Path::new(&string))
+                .context(format!("could not resolve local_path {}", string))?,
             _ => bail!("local_path is not a string"),
         },
         _ => bail!("local_path is not given"),
@@ -232,6 +607,255 @@ fn parse_remote_transfer<T: RemoteTransfer>(value: &Value) -> Result<T> {
     Ok(T::new(address, username, password, remote_path, local_path).context("Could not create Task for remote transfer operation")?)
 }
 
+/// Parses a file mode. Numbers are the mode itself, e.g. `0o644`, and strings are read as octal,
+/// e.g. `"644"`, `"0644"` or `"0o644"`.
+fn parse_mode(value: &Value) -> Result<u32> {
+    let text = match value {
+        Value::Number(number) => match number.as_u64() {
+            Some(mode) if mode <= 0o7777 => return Ok(mode as u32),
+            _ => bail!(format!("mode {} is not a number up to 0o7777", number)),
+        },
+        Value::String(text) => text.trim_start_matches("0o").to_string(),
+        _ => bail!("mode is not an octal number"),
+    };
+    match u32::from_str_radix(&text, 8) {
+        Ok(mode) if mode <= 0o7777 => Ok(mode),
+        _ => bail!(format!("mode {} is not an octal number up to 7777", text)),
+    }
+}
+
+/// Gets a path that is resolved like the other local paths.
+fn parse_local_path(workflow: &Workflow, map: &Mapping, key: &str) -> Result<PathBuf> {
+    match get_entry(map, key.into()) {
+        Some(Value::String(path)) => workflow
+            .resolve_path(Path::new(&path))
+            .context(format!("could not resolve {} {}", key, path)),
+        Some(_) => bail!(format!("{} is not a string", key)),
+        None => bail!(format!("{} is not given", key)),
+    }
+}
+
+fn parse_overwrite(map: &Mapping) -> Result<Overwrite> {
+    match get_entry(map, "overwrite".into()) {
+        Some(Value::String(overwrite)) => match overwrite.as_str() {
+            "always" => Ok(Overwrite::Always),
+            "never" => Ok(Overwrite::Never),
+            "error" => Ok(Overwrite::Error),
+            _ => bail!(format!(
+                "overwrite {} is not one of always, never and error",
+                overwrite
+            )),
+        },
+        Some(_) => bail!("overwrite is not a string"),
+        None => Ok(Overwrite::Always),
+    }
+}
+
+fn parse_optional_mode(map: &Mapping) -> Result<Option<u32>> {
+    match get_entry(map, "mode".into()) {
+        Some(mode) => Ok(Some(parse_mode(&mode)?)),
+        None => Ok(None),
+    }
+}
+
+/// Gets the map of a task that can also be given as a single path.
+fn path_task_map(value: &Value) -> Result<Mapping> {
+    match value {
+        Value::String(path) => {
+            let mut map = Mapping::new();
+            map.insert("path".into(), Value::String(path.clone()));
+            Ok(map)
+        }
+        Value::Mapping(map) => Ok(map.clone()),
+        _ => bail!("value is neither a path nor a map"),
+    }
+}
+
+fn parse_copy(workflow: &Workflow, value: &Value) -> Result<CopyTask> {
+    let map = value.as_mapping().context("copy is not a map")?;
+    Ok(CopyTask::new(
+        parse_local_path(workflow, map, "src")?,
+        parse_local_path(workflow, map, "dest")?,
+        parse_overwrite(map)?,
+    ))
+}
+
+fn parse_move(workflow: &Workflow, value: &Value) -> Result<MoveTask> {
+    let map = value.as_mapping().context("move is not a map")?;
+    Ok(MoveTask::new(
+        parse_local_path(workflow, map, "src")?,
+        parse_local_path(workflow, map, "dest")?,
+        parse_overwrite(map)?,
+    ))
+}
+
+fn parse_remove(workflow: &Workflow, value: &Value) -> Result<RemoveTask> {
+    let map = path_task_map(value)?;
+    Ok(RemoveTask::new(parse_local_path(workflow, &map, "path")?))
+}
+
+fn parse_mkdir(workflow: &Workflow, value: &Value) -> Result<MkdirTask> {
+    let map = path_task_map(value)?;
+    Ok(MkdirTask::new(
+        parse_local_path(workflow, &map, "path")?,
+        parse_optional_mode(&map)?,
+    ))
+}
+
+fn parse_content(map: &Mapping) -> Result<String> {
+    match get_entry(map, "content".into()) {
+        Some(Value::Sequence(_) | Value::Mapping(_)) => bail!("content is not a string"),
+        Some(content) => template::value_to_string(&content),
+        None => bail!("content is not given"),
+    }
+}
+
+fn parse_write_file(workflow: &Workflow, value: &Value) -> Result<WriteFileTask> {
+    let map = value.as_mapping().context("write-file is not a map")?;
+    Ok(WriteFileTask::new(
+        parse_local_path(workflow, map, "path")?,
+        parse_content(map)?,
+        parse_overwrite(map)?,
+        parse_optional_mode(map)?,
+    ))
+}
+
+fn parse_append_file(workflow: &Workflow, value: &Value) -> Result<WriteFileTask> {
+    let map = value.as_mapping().context("append-file is not a map")?;
+    Ok(WriteFileTask::append(
+        parse_local_path(workflow, map, "path")?,
+        parse_content(map)?,
+        parse_optional_mode(map)?,
+    ))
+}
+
+fn parse_chmod(workflow: &Workflow, value: &Value) -> Result<ChmodTask> {
+    let map = value.as_mapping().context("chmod is not a map")?;
+    let mode = match get_entry(map, "mode".into()) {
+        Some(mode) => parse_mode(&mode)?,
+        None => bail!("mode is not given"),
+    };
+    let recursive = match get_entry(map, "recursive".into()) {
+        Some(Value::Bool(recursive)) => recursive,
+        Some(_) => bail!("recursive is not a bool"),
+        None => false,
+    };
+    Ok(ChmodTask::new(
+        parse_local_path(workflow, map, "path")?,
+        mode,
+        recursive,
+    ))
+}
+
+fn parse_symlink(workflow: &Workflow, value: &Value) -> Result<SymlinkTask> {
+    let map = value.as_mapping().context("symlink is not a map")?;
+    Ok(SymlinkTask::new(
+        parse_local_path(workflow, map, "src")?,
+        parse_local_path(workflow, map, "dest")?,
+        parse_overwrite(map)?,
+    ))
+}
+
+/// Gets the destination of a task. With an `address` it is a path on the remote computer.
+fn parse_destination(workflow: &Workflow, map: &Mapping, dest: String) -> Result<Destination> {
+    // with an address the destination is a path on the remote computer
+    match get_entry(map, "address".into()) {
+        Some(Value::String(address)) => {
+            let username = match get_entry(map, "username".into()) {
+                Some(Value::String(username)) => username,
+                Some(_) => bail!("username is not a string"),
+                None => bail!("username is not given"),
+            };
+            let password = match get_entry(map, "password".into()) {
+                Some(Value::String(password)) => password,
+                Some(_) => bail!("password is not a string"),
+                None => bail!("password is not given"),
+            };
+            Ok(Destination::Remote {
+                address: Ipv4Addr::from_str(&address)
+                    .context(format!("address {} is not valid", address))?,
+                user: username,
+                password,
+                path: PathBuf::from(dest),
+            })
+        }
+        Some(_) => bail!("address is not a string"),
+        None => Ok(Destination::Local(
+            workflow
+                .resolve_path(Path::new(&dest))
+                .context(format!("could not resolve dest {}", dest))?,
+        )),
+    }
+}
+
+fn parse_template(workflow: &Workflow, value: &Value) -> Result<TemplateTask> {
+    let map = value.as_mapping().context("template is not a map")?;
+    let src = match get_entry(map, "src".into()) {
+        Some(Value::String(src)) => workflow
+            .resolve_path(Path::new(&src))
+            .context(format!("could not resolve src {}", src))?,
+        Some(_) => bail!("src is not a string"),
+        None => bail!("src is not given"),
+    };
+    let dest = match get_entry(map, "dest".into()) {
+        Some(Value::String(dest)) => dest,
+        Some(_) => bail!("dest is not a string"),
+        None => bail!("dest is not given"),
+    };
+    let mode = match get_entry(map, "mode".into()) {
+        Some(mode) => Some(parse_mode(&mode)?),
+        None => None,
+    };
+    let dest = parse_destination(workflow, map, dest)?;
+    Ok(TemplateTask::new(src, dest, mode, workflow.vars.clone()))
+}
+
+/// Gets the format of an archive from `format` or the extension of the path.
+fn parse_archive_format(map: &Mapping, path: &Path) -> Result<Format> {
+    match get_entry(map, "format".into()) {
+        Some(Value::String(name)) => Format::from_name(&name).context(format!(
+            "format {} is not one of tar, tar.gz, tar.zst and zip",
+            name
+        )),
+        Some(_) => bail!("format is not a string"),
+        None => Format::from_path(path).context(format!(
+            "the format of {:?} is not known by its extension, give a format",
+            path
+        )),
+    }
+}
+
+fn parse_archive(workflow: &Workflow, value: &Value) -> Result<ArchiveTask> {
+    let map = value.as_mapping().context("archive is not a map")?;
+    let dest = parse_local_path(workflow, map, "dest")?;
+    let include = match get_entry(map, "include".into()) {
+        Some(value) => parse_string_list(value).context("include is invalid")?,
+        None => Vec::new(),
+    };
+    let exclude = match get_entry(map, "exclude".into()) {
+        Some(value) => parse_string_list(value).context("exclude is invalid")?,
+        None => Vec::new(),
+    };
+    Ok(ArchiveTask::new(
+        parse_local_path(workflow, map, "src")?,
+        dest.clone(),
+        parse_archive_format(map, &dest)?,
+        Filter::new(&include, &exclude)?,
+    ))
+}
+
+fn parse_extract(workflow: &Workflow, value: &Value) -> Result<ExtractTask> {
+    let map = value.as_mapping().context("extract is not a map")?;
+    let src = parse_local_path(workflow, map, "src")?;
+    let dest = match get_entry(map, "dest".into()) {
+        Some(Value::String(dest)) => parse_destination(workflow, map, dest)?,
+        Some(_) => bail!("dest is not a string"),
+        None => bail!("dest is not given"),
+    };
+    let format = parse_archive_format(map, &src)?;
+    Ok(ExtractTask::new(src, dest, format))
+}
+
 fn parse_ssh(value: &Value) -> Result<SshTask> {
     if !value.is_mapping() {
         bail!("Value is not of type Mapping");
@@ -276,7 +900,15 @@ fn parse_ssh(value: &Value) -> Result<SshTask> {
         commands.push(parse_ssh_command(&item).context("parsing of ssh command failed")?);
     }
 
-    Ok(SshTask::new(address, username, password, commands))
+    let interrupt = match get_entry(value.as_mapping().unwrap(), "interrupt".into()) {
+        Some(Value::Bool(interrupt)) => interrupt,
+        Some(_) => bail!("interrupt is not a bool"),
+        None => false,
+    };
+
+    Ok(SshTask::new(
+        address, username, password, commands, interrupt,
+    ))
 }
 
 fn parse_ssh_command(value: &Value) -> Result<SshCommand> {
@@ -332,7 +964,7 @@ fn parse_ssh_command(value: &Value) -> Result<SshCommand> {
     }
 }
 
-fn parse_os_dependent(root_map: &Mapping, os: OS, value: &Value) -> Result<OSDependent> {
+fn parse_os_dependent(workflow: &Workflow, os: OS, value: &Value) -> Result<OSDependent> {
     if !value.is_sequence() {
         bail!("value is not a sequence");
     }
@@ -340,7 +972,7 @@ fn parse_os_dependent(root_map: &Mapping, os: OS, value: &Value) -> Result<OSDep
     let mut task = OSDependent::new(os);
     for child_item in value.as_sequence().unwrap() {
         task.add_child(
-            parse_task(root_map, child_item)
+            parse_task(workflow, child_item)
                 .context(format!("could not parse child task for {}", task))?,
         );
     }
@@ -348,7 +980,8 @@ fn parse_os_dependent(root_map: &Mapping, os: OS, value: &Value) -> Result<OSDep
     Ok(task)
 }
 
-fn parse_shell_command_task<T: ShellCommand>(value: &Value) -> Result<T> {
+#[allow(clippy::needless_return)]
+fn parse_shell_command_task<T: ShellCommand>(workflow: &Workflow, value: &Value) -> Result<T> {
     match value {
         Value::Mapping(cmd_map) => {
             let command_value = match get_entry(cmd_map, "command".into()) {
@@ -361,7 +994,13 @@ fn parse_shell_command_task<T: ShellCommand>(value: &Value) -> Result<T> {
 
             let work_dir_value = match get_entry(cmd_map, "work_dir".into()) {
                 Some(entry) => match entry {
-                    Value::String(string) => Some(string),
+                    Value::String(string) => Some(
+                        workflow
+                            .resolve_path(Path::new(&string))
+                            .context(format!("could not resolve work_dir {}", string))?
+                            .to_string_lossy()
+                            .into_owned(),
+                    ),
                     _ => bail!("command is not a string"),
                 },
                 _ => None,
@@ -391,10 +1030,20 @@ fn parse_shell_command_task<T: ShellCommand>(value: &Value) -> Result<T> {
                 _ => None,
             };
 
+            let register = match get_entry(cmd_map, "register".into()) {
+                Some(Value::String(name)) => Some(name),
+                Some(_) => bail!("register is not a string"),
+                None => None,
+            };
+
+            let incremental = parse_incremental(workflow, cmd_map)?;
+
             return Ok(T::new(
                 command_value.split(' ').map(|x| x.to_string()).collect(),
                 work_dir_value,
                 allowed_exit_codes,
+                register,
+                incremental,
             ));
         }
         val => match val {
@@ -404,6 +1053,8 @@ fn parse_shell_command_task<T: ShellCommand>(value: &Value) -> Result<T> {
                     string.split(' ').map(|x| x.to_string()).collect(),
                     None,
                     None,
+                    None,
+                    None,
                 ));
             }
             _ => bail!("task has a problem with its definition"),
@@ -411,19 +1062,308 @@ fn parse_shell_command_task<T: ShellCommand>(value: &Value) -> Result<T> {
     }
 }
 
-/// Parses the file and returns a vector of the found jobs.
-pub fn jobs_from_file(path: PathBuf) -> Result<Vec<Job>> {
-    let mut value = read_yaml_file(path).context("reading yaml error")?;
-    render::render(&mut std::collections::HashMap::new(), &mut value)
-        .context("resolving yaml error")?; // pre render everything
-    Ok(parse_jobs(value.as_mapping().unwrap().to_owned()))
+/// Parses a string or a sequence of strings.
+fn parse_string_list(value: Value) -> Result<Vec<String>> {
+    match value {
+        Value::String(string) => Ok(vec![string]),
+        Value::Sequence(seq) => seq
+            .into_iter()
+            .map(|item| match item {
+                Value::String(string) => Ok(string),
+                _ => bail!("{:?} is not a string", item),
+            })
+            .collect(),
+        _ => bail!("is not a string or a sequence of strings"),
+    }
+}
+
+/// Parses the `sources` and `outputs` of a task. Both are resolved against the directory of the
+/// workflow file.
+fn parse_incremental(workflow: &Workflow, map: &Mapping) -> Result<Option<Incremental>> {
+    let sources = match get_entry(map, "sources".into()) {
+        Some(value) => parse_string_list(value).context("sources are invalid")?,
+        None => {
+            if get_entry(map, "outputs".into()).is_some() {
+                bail!("outputs are given without sources");
+            }
+            return Ok(None);
+        }
+    };
+    let mut resolved_sources = Vec::new();
+    for source in sources {
+        glob::Pattern::new(&source).context(format!("source {} is not a valid glob", source))?;
+        resolved_sources.push(
+            workflow
+                .resolve_path(Path::new(&source))
+                .context(format!("could not resolve source {}", source))?
+                .to_string_lossy()
+                .into_owned(),
+        );
+    }
+    let mut outputs = Vec::new();
+    if let Some(value) = get_entry(map, "outputs".into()) {
+        for output in parse_string_list(value).context("outputs are invalid")? {
+            outputs.push(
+                workflow
+                    .resolve_path(Path::new(&output))
+                    .context(format!("could not resolve output {}", output))?,
+            );
+        }
+    }
+    Ok(Some(Incremental::new(resolved_sources, outputs)))
+}
+
+/// Top level keys that are not jobs.
+const RESERVED_KEYS: [&str; 3] = ["IGNORE", "LOG", "INCLUDE"];
+
+/// A workflow file that has been read and rendered, with the jobs of the files it includes.
+#[derive(Clone)]
+pub struct Workflow {
+    root_map: Mapping,
+    /// the directory of the file, relative paths in tasks are resolved against it
+    base_dir: PathBuf,
+    /// the namespace the names of jobs are looked up in first, see [`include::Scope`]
+    namespace: String,
+    /// the scope of every job
+    scopes: HashMap<String, include::Scope>,
+    /// the workflow file followed by the included files
+    files: Vec<PathBuf>,
+    /// the prompts and answers of the non hidden inputs
+    inputs: Vec<(String, String)>,
+    /// the params of the job and the variables of the loops that are parsed, they are rendered
+    /// into `template` tasks
+    vars: HashMap<String, Value>,
+}
+
+/// The name and description of a job in a [`Workflow`].
+pub struct JobInfo {
+    pub name: String,
+    pub description: Option<String>,
+    pub params: Vec<String>,
+}
+
+impl Workflow {
+    /// Reads the file and the files it includes and renders all tags.
+    pub fn from_file(path: PathBuf) -> Result<Self> {
+        let loaded = include::load(&path)?;
+        Ok(Self {
+            root_map: loaded.root_map,
+            base_dir: loaded.base_dir,
+            namespace: String::new(),
+            scopes: loaded.scopes,
+            files: loaded.files,
+            inputs: render::take_answers(),
+            vars: HashMap::new(),
+        })
+    }
+
+    /// Resolves `~` and paths relative to the directory of the workflow file.
+    fn resolve_path(&self, path: &Path) -> Result<PathBuf> {
+        Ok(path.try_resolve_in(&self.base_dir)?.into_owned())
+    }
+
+    /// Finds a job by its name in the namespace of the workflow and then by its full name.
+    /// Returns the full name and the definition.
+    fn find_job(&self, name: &str) -> Option<(String, Value)> {
+        if RESERVED_KEYS.contains(&name) {
+            return None;
+        }
+        let names = [format!("{}{}", self.namespace, name), name.to_string()];
+        names.into_iter().find_map(|name| {
+            get_entry(&self.root_map, name.clone().into()).map(|value| (name, value))
+        })
+    }
+
+    /// Returns the workflow in the scope of the file the job was defined in.
+    fn scoped(&self, job: &str) -> Self {
+        let mut workflow = self.clone();
+        if let Some(scope) = self.scopes.get(job) {
+            workflow.base_dir = scope.base_dir.clone();
+            workflow.namespace = scope.namespace.clone();
+        }
+        workflow
+    }
+
+    /// Returns the workflow file followed by the files it includes.
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+
+    /// Returns the prompts and answers of the inputs that are not hidden.
+    pub fn inputs(&self) -> &[(String, String)] {
+        &self.inputs
+    }
+
+    /// Returns the names of all jobs.
+    pub fn job_names(&self) -> Vec<String> {
+        self.root_map
+            .keys()
+            .filter_map(|key| key.as_str())
+            .filter(|name| !RESERVED_KEYS.contains(name))
+            .map(|name| name.to_string())
+            .collect()
+    }
+
+    /// Returns the name, description and params of every job.
+    pub fn job_infos(&self) -> Vec<JobInfo> {
+        let mut infos = Vec::new();
+        for name in self.job_names() {
+            let mut info = JobInfo {
+                name: name.clone(),
+                description: None,
+                params: Vec::new(),
+            };
+            if let Some(Value::Mapping(job_map)) = get_entry(&self.root_map, name.into()) {
+                if let Some(Value::String(description)) = get_entry(&job_map, "description".into())
+                {
+                    info.description = Some(description);
+                }
+                if let Some(Ok(params)) =
+                    get_entry(&job_map, "params".into()).map(|value| params::parse_params(&value))
+                {
+                    info.params = params
+                        .into_iter()
+                        .map(|param| match param.default {
+                            Some(default) => format!(
+                                "{}={}",
+                                param.name,
+                                template::value_to_string(&default).unwrap_or_default()
+                            ),
+                            None => param.name,
+                        })
+                        .collect();
+                }
+            }
+            infos.push(info);
+        }
+        infos
+    }
+
+    /// Parses the job with the given name. The arguments are used for the params of the job.
+    ///
+    /// Returns `None` if the job does not exist.
+    pub fn job(&self, name: &str, args: &HashMap<String, Value>) -> Result<Option<Job>> {
+        if self.find_job(name).is_none() {
+            return Ok(None);
+        }
+        Ok(Some(parse_job(self, name.to_string(), args)?))
+    }
+
+    /// Parses the `LOG` section. It is either the directory of the logs or a map with `dir`,
+    /// `keep` and `max_age_days`.
+    ///
+    /// Returns `None` if there is no `LOG` section.
+    pub fn log_config(&self) -> Result<Option<LogConfig>> {
+        let log_map = match get_entry(&self.root_map, "LOG".into()) {
+            None => return Ok(None),
+            Some(Value::String(dir)) => {
+                return Ok(Some(LogConfig::new(self.resolve_path(Path::new(&dir))?)))
+            }
+            Some(Value::Mapping(log_map)) => log_map,
+            Some(_) => bail!("LOG is not a string or map"),
+        };
+
+        let dir = match get_entry(&log_map, "dir".into()) {
+            Some(Value::String(dir)) => self.resolve_path(Path::new(&dir))?,
+            Some(_) => bail!("dir of LOG is not a string"),
+            None => bail!("dir of LOG is not given"),
+        };
+        let mut config = LogConfig::new(dir);
+        config.keep = match get_entry(&log_map, "keep".into()) {
+            Some(Value::Number(keep)) => Some(
+                keep.as_u64()
+                    .context("keep of LOG is not a positive integer")? as usize,
+            ),
+            Some(_) => bail!("keep of LOG is not a positive integer"),
+            None => None,
+        };
+        config.max_age = match get_entry(&log_map, "max_age_days".into()) {
+            Some(Value::Number(days)) => Some(Duration::from_secs_f64(
+                days.as_f64()
+                    .filter(|days| *days >= 0.0)
+                    .context("max_age_days of LOG is not a positive number")?
+                    * 24.0
+                    * 60.0
+                    * 60.0,
+            )),
+            Some(_) => bail!("max_age_days of LOG is not a positive number"),
+            None => None,
+        };
+        Ok(Some(config))
+    }
+
+    /// Parses every job and returns all errors.
+    ///
+    /// Jobs with required params can not be parsed without arguments, so only their params are
+    /// checked. Their names are returned as the second value.
+    pub fn validate(&self) -> (Vec<anyhow::Error>, Vec<String>) {
+        let mut errors = Vec::new();
+        let mut not_parsed = Vec::new();
+
+        for key in self.root_map.keys() {
+            if !key.is_string() {
+                errors.push(anyhow::anyhow!(format!(
+                    "Job {:?} has not a valid string as name",
+                    key
+                )));
+            }
+        }
+
+        if let Err(error) = self.log_config() {
+            errors.push(error.context("Error in LOG"));
+        }
+
+        for name in self.job_names() {
+            if let Some(Value::Mapping(job_map)) = get_entry(&self.root_map, name.clone().into()) {
+                if let Some(params_value) = get_entry(&job_map, "params".into()) {
+                    match params::parse_params(&params_value) {
+                        Ok(params) if params.iter().any(|param| param.default.is_none()) => {
+                            not_parsed.push(name);
+                            continue;
+                        }
+                        Ok(_) => {}
+                        Err(error) => {
+                            errors.push(error.context(format!("Error in params of job {}", name)));
+                            continue;
+                        }
+                    }
+                }
+            }
+            if let Err(error) = parse_job(self, name, &HashMap::new()) {
+                errors.push(error);
+            }
+        }
+        (errors, not_parsed)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use serde_yaml::Value;
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+
+    use crate::{
+        tasks::ssh::SshCommand,
+        tasks::Task,
+        yaml_parser::{
+            parse_approve, parse_job, parse_mode, parse_ssh, parse_ssh_command, Workflow,
+        },
+    };
 
-    use crate::{tasks::ssh::SshCommand, yaml_parser::parse_ssh_command};
+    /// Returns a workflow of the yaml in `/srv/project`.
+    fn workflow(yaml: &str) -> Workflow {
+        let value: Value = serde_yaml::from_str(yaml).unwrap();
+        Workflow {
+            root_map: value.as_mapping().unwrap().to_owned(),
+            base_dir: PathBuf::from("/srv/project"),
+            namespace: String::new(),
+            scopes: HashMap::new(),
+            files: Vec::new(),
+            inputs: Vec::new(),
+            vars: HashMap::new(),
+        }
+    }
 
     #[test]
     fn parse_ssh_command_test_simple() {
@@ -454,4 +1394,139 @@ mod tests {
             SshCommand::new("ls 2".to_string(), vec![1, 2, 3, 4, 5])
         );
     }
+
+    #[test]
+    fn parse_job_call_test() {
+        let workflow = workflow(
+            "
+        greet:
+            params:
+                name:
+                greeting: 'Hello'
+            tasks:
+                - print: '{{ greeting }} {{ name }}!'
+        example:
+            - call: {job: greet, with: {name: 'World'}}
+        missing:
+            - call: greet
+        ",
+        );
+        let job = parse_job(&workflow, "example".to_string(), &HashMap::new()).unwrap();
+        assert_eq!(
+            job.to_string(),
+            "Job: { name \"example\" children { Job: { name \"greet\" children { PrintTask { prompt: \"Hello World!\" } } } } }"
+        );
+
+        // the cause is kept in the chain of the error
+        let error = parse_job(&workflow, "missing".to_string(), &HashMap::new())
+            .err()
+            .unwrap();
+        assert!(format!("{:#}", error).contains("parameter name is required"));
+    }
+
+    #[test]
+    fn describe_ssh_without_password_test() {
+        let value: Value = serde_yaml::from_str(
+            "
+        address: 192.168.0.2
+        username: 'user'
+        password: 'secret_password'
+        commands: ['ls', 'uptime']
+        ",
+        )
+        .unwrap();
+        assert_eq!(
+            parse_ssh(&value).unwrap().describe(),
+            "ssh user@192.168.0.2: ls; uptime"
+        );
+    }
+
+    #[test]
+    fn resolve_relative_work_dir_test() {
+        let workflow = workflow(
+            "
+        example:
+            - bash:
+                command: 'make'
+                work_dir: 'build'
+        ",
+        );
+        let job = parse_job(&workflow, "example".to_string(), &HashMap::new()).unwrap();
+        assert!(job
+            .to_string()
+            .contains("work_dir: Some(\"/srv/project/build\")"));
+    }
+
+    #[test]
+    fn parse_approve_test() {
+        let workflow = workflow(
+            "
+        example:
+            - print: 'before'
+            - approve: {message: 'Deploy?', timeout: 30}
+            - print: 'deploying'
+        ",
+        );
+        let job = parse_job(&workflow, "example".to_string(), &HashMap::new()).unwrap();
+        assert_eq!(
+            job.to_string(),
+            "Job: { name \"example\" children { PrintTask { prompt: \"before\" } ApproveTask { message: \"Deploy?\", timeout: Some(30s), on_timeout: Abort, summary: [\"print \\\"deploying\\\"\"] } PrintTask { prompt: \"deploying\" } } }"
+        );
+
+        let value: Value = serde_yaml::from_str("{message: 'Deploy?', on_timeout: wait}").unwrap();
+        assert!(parse_approve(&value).is_err());
+    }
+
+    #[test]
+    fn parse_mode_test() {
+        let mode = |yaml| parse_mode(&serde_yaml::from_str(yaml).unwrap());
+        assert_eq!(mode("0o644").unwrap(), 0o644);
+        assert_eq!(mode("'644'").unwrap(), 0o644);
+        assert_eq!(mode("'0755'").unwrap(), 0o755);
+        assert_eq!(mode("'0o4755'").unwrap(), 0o4755);
+        assert!(mode("0o10000").is_err());
+        assert!(mode("'abc'").is_err());
+    }
+
+    #[test]
+    fn check_loop_tasks_test() {
+        let workflow = workflow(
+            "
+        valid:
+            - foreach:
+                glob: '*.txt'
+                tasks:
+                    - print: '{{ item }}'
+                    - chmod: {path: 'a', mode: '{{ item }}'}
+            - foreach:
+                lines: modes
+                as: mode
+                tasks:
+                    - chmod: {path: 'a', mode: '{{ mode }}'}
+            - matrix:
+                axes: {mode: [0o644]}
+                tasks:
+                    - chmod: {path: 'a', mode: '{{ mode }}'}
+        typo:
+            - foreach:
+                items: [1, 2]
+                tasks:
+                    - prnit: '{{ item }}'
+        glob_typo:
+            - foreach:
+                glob: '*.txt'
+                tasks:
+                    - prnit: 'done'
+        invalid_mode:
+            - matrix:
+                axes: {mode: ['abc']}
+                tasks:
+                    - chmod: {path: 'a', mode: '{{ mode }}'}
+        ",
+        );
+        assert!(parse_job(&workflow, "valid".to_string(), &HashMap::new()).is_ok());
+        assert!(parse_job(&workflow, "typo".to_string(), &HashMap::new()).is_err());
+        assert!(parse_job(&workflow, "glob_typo".to_string(), &HashMap::new()).is_err());
+        assert!(parse_job(&workflow, "invalid_mode".to_string(), &HashMap::new()).is_err());
+    }
 }
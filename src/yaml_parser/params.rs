@@ -0,0 +1,252 @@
+use super::get_entry;
+use super::template::value_to_string;
+use crate::mask;
+use anyhow::{bail, Context, Result};
+use serde_yaml::{Mapping, Value};
+use std::collections::HashMap;
+
+#[derive(Debug, PartialEq)]
+pub enum ParamType {
+    String,
+    Int,
+    Float,
+    Bool,
+}
+
+impl ParamType {
+    fn from_name(name: &str) -> Result<Self> {
+        match name {
+            "string" => Ok(Self::String),
+            "int" => Ok(Self::Int),
+            "float" => Ok(Self::Float),
+            "bool" => Ok(Self::Bool),
+            other => bail!(format!(
+                "{} is not a valid parameter type (string, int, float, bool)",
+                other
+            )),
+        }
+    }
+
+    fn of_value(value: &Value) -> Result<Self> {
+        match value {
+            Value::String(_) => Ok(Self::String),
+            Value::Bool(_) => Ok(Self::Bool),
+            Value::Number(number) if number.is_f64() => Ok(Self::Float),
+            Value::Number(_) => Ok(Self::Int),
+            other => bail!(format!("{:?} is not a valid parameter value", other)),
+        }
+    }
+
+    /// Converts the value into this type. Strings are parsed, so arguments from the cli can be used.
+    fn convert(&self, value: &Value) -> Result<Value> {
+        Ok(match (self, value) {
+            (Self::String, Value::String(_)) => value.to_owned(),
+            (Self::String, Value::Number(number)) => Value::String(number.to_string()),
+            (Self::String, Value::Bool(boolean)) => Value::String(boolean.to_string()),
+            (Self::Int, Value::Number(number)) if number.is_i64() || number.is_u64() => {
+                value.to_owned()
+            }
+            (Self::Int, Value::String(string)) => Value::Number(
+                string
+                    .parse::<i64>()
+                    .context(format!("{} is not an int", string))?
+                    .into(),
+            ),
+            (Self::Float, Value::Number(number)) => {
+                Value::Number(number.as_f64().context("number is not a float")?.into())
+            }
+            (Self::Float, Value::String(string)) => Value::Number(
+                string
+                    .parse::<f64>()
+                    .context(format!("{} is not a float", string))?
+                    .into(),
+            ),
+            (Self::Bool, Value::Bool(_)) => value.to_owned(),
+            (Self::Bool, Value::String(string)) => Value::Bool(
+                string
+                    .parse::<bool>()
+                    .context(format!("{} is not a bool", string))?,
+            ),
+            (param_type, other) => bail!(format!("{:?} is not of type {:?}", other, param_type)),
+        })
+    }
+}
+
+/// A parameter that is declared by a job.
+#[derive(Debug, PartialEq)]
+pub struct JobParam {
+    pub name: String,
+    pub param_type: ParamType,
+    pub default: Option<Value>,
+    /// the value is a secret that is masked in all output
+    pub sensitive: bool,
+}
+
+/// Parses the `params` map of a job.
+///
+/// A parameter can be declared without a value (required string), with a default value or with a
+/// map containing `type`, `default` and `sensitive`.
+pub fn parse_params(value: &Value) -> Result<Vec<JobParam>> {
+    let map = match value {
+        Value::Mapping(map) => map,
+        _ => bail!("params is not a map"),
+    };
+
+    let mut params = Vec::new();
+    for (key, param_value) in map {
+        let name = match key {
+            Value::String(name) => name.to_owned(),
+            _ => bail!(format!("parameter name {:?} is not a string", key)),
+        };
+        let param = match param_value {
+            Value::Null => JobParam {
+                name,
+                param_type: ParamType::String,
+                default: None,
+                sensitive: false,
+            },
+            Value::Mapping(param_map) => parse_param_map(name.clone(), param_map)
+                .context(format!("invalid definition of parameter {}", name))?,
+            default => JobParam {
+                param_type: ParamType::of_value(default)
+                    .context(format!("invalid default of parameter {}", name))?,
+                name,
+                default: Some(default.to_owned()),
+                sensitive: false,
+            },
+        };
+        params.push(param);
+    }
+    Ok(params)
+}
+
+fn parse_param_map(name: String, map: &Mapping) -> Result<JobParam> {
+    let default = get_entry(map, "default".into());
+
+    let param_type = match get_entry(map, "type".into()) {
+        Some(Value::String(type_name)) => ParamType::from_name(&type_name)?,
+        Some(_) => bail!("type is not a string"),
+        None => match &default {
+            Some(default) => ParamType::of_value(default)?,
+            None => ParamType::String,
+        },
+    };
+
+    let default = match default {
+        Some(default) => Some(
+            param_type
+                .convert(&default)
+                .context("default does not match the type")?,
+        ),
+        None => None,
+    };
+
+    let sensitive = match get_entry(map, "sensitive".into()) {
+        Some(Value::Bool(sensitive)) => sensitive,
+        Some(_) => bail!("sensitive is not a bool"),
+        None => false,
+    };
+
+    Ok(JobParam {
+        name,
+        param_type,
+        default,
+        sensitive,
+    })
+}
+
+/// Builds the variables for a job from its parameters and the given arguments.
+pub fn resolve_args(
+    params: &[JobParam],
+    args: &HashMap<String, Value>,
+) -> Result<HashMap<String, Value>> {
+    for name in args.keys() {
+        if !params.iter().any(|param| &param.name == name) {
+            bail!(format!("unknown parameter {}", name));
+        }
+    }
+
+    let mut vars = HashMap::new();
+    for param in params {
+        let value = match (args.get(&param.name), &param.default) {
+            (Some(arg), _) => param
+                .param_type
+                .convert(arg)
+                .context(format!("invalid argument for parameter {}", param.name))?,
+            (None, Some(default)) => default.to_owned(),
+            (None, None) => bail!(format!("parameter {} is required", param.name)),
+        };
+        if param.sensitive {
+            mask::register(&value_to_string(&value)?);
+        }
+        vars.insert(param.name.clone(), value);
+    }
+    Ok(vars)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_params, resolve_args, JobParam, ParamType};
+    use serde_yaml::Value;
+    use std::collections::HashMap;
+
+    #[test]
+    fn parse_params_test() {
+        let value: Value = serde_yaml::from_str(
+            "
+        host:
+        version: '1.0'
+        retries: {type: int, default: 3, sensitive: true}
+        ",
+        )
+        .unwrap();
+        assert_eq!(
+            parse_params(&value).unwrap(),
+            vec![
+                JobParam {
+                    name: "host".to_string(),
+                    param_type: ParamType::String,
+                    default: None,
+                    sensitive: false
+                },
+                JobParam {
+                    name: "version".to_string(),
+                    param_type: ParamType::String,
+                    default: Some(Value::String("1.0".to_string())),
+                    sensitive: false
+                },
+                JobParam {
+                    name: "retries".to_string(),
+                    param_type: ParamType::Int,
+                    default: Some(Value::Number(3.into())),
+                    sensitive: true
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn resolve_args_test() {
+        let value: Value = serde_yaml::from_str(
+            "
+        host:
+        retries: {type: int, default: 3}
+        ",
+        )
+        .unwrap();
+        let params = parse_params(&value).unwrap();
+
+        let mut args = HashMap::new();
+        args.insert("retries".to_string(), Value::String("5".to_string()));
+        // host is required
+        assert!(resolve_args(&params, &args).is_err());
+
+        args.insert("host".to_string(), Value::String("a".to_string()));
+        let vars = resolve_args(&params, &args).unwrap();
+        assert_eq!(vars["retries"], Value::Number(5.into()));
+        assert_eq!(vars["host"], Value::String("a".to_string()));
+
+        args.insert("unknown".to_string(), Value::String("a".to_string()));
+        assert!(resolve_args(&params, &args).is_err());
+    }
+}
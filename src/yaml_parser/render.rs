@@ -1,33 +1,71 @@
 use super::get_entry;
+use crate::mask;
+use crate::secrets;
+use crate::util::to_hex;
 use anyhow::{bail, Context, Result};
+use resolve_path::PathResolveExt;
 use serde_yaml::{self, Value};
+use std::cell::RefCell;
 use std::collections::HashMap;
 use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
 
-/// resolves all tagged values recursively
-pub fn render(_ids: &mut HashMap<String, Value>, value: &mut Value) -> Result<()> {
+/// The prompts and answers of all non hidden inputs.
+static ANSWERS: Mutex<Vec<(String, String)>> = Mutex::new(Vec::new());
+
+thread_local! {
+    /// the yaml files that are being read by `!File`, to detect cycles
+    static FILE_STACK: RefCell<Vec<PathBuf>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Returns the prompts and answers of the non hidden inputs since the last call.
+pub fn take_answers() -> Vec<(String, String)> {
+    std::mem::take(&mut *ANSWERS.lock().unwrap())
+}
+
+/// resolves all tagged values recursively. Relative paths in tags are resolved against `base_dir`.
+pub fn render(_ids: &mut HashMap<String, Value>, base_dir: &Path, value: &mut Value) -> Result<()> {
     match value {
         Value::Mapping(map) => {
             for map_value in map.values_mut() {
-                render(_ids, map_value)?;
+                render(_ids, base_dir, map_value)?;
             }
         }
         Value::Sequence(seq) => {
             for item in seq {
-                render(_ids, item)?;
+                render(_ids, base_dir, item)?;
             }
         }
         Value::Tagged(tagged) => {
             let mut new_value = match tagged.tag.to_string().as_str() {
-                "!Input" => render_tag_input(_ids, &mut tagged.value, false)
+                "!Input" => render_tag_input(_ids, base_dir, &mut tagged.value, false)
                     .context("failed to resolve !Input")?,
-                "!HiddenInput" => render_tag_input(_ids, &mut tagged.value, true)
+                "!HiddenInput" => render_tag_input(_ids, base_dir, &mut tagged.value, true)
                     .context("failed to resolve !HiddenInput")?,
-                "!StrF" => {
-                    render_tag_strf(_ids, &tagged.value).context("failed to resolve !StrF")?
-                }
-                "!Id" => render_tag_id(_ids, &mut tagged.value).context("failed to resolve !Id")?,
-                _ => bail!(format!("{} is not a valid tag", tagged.tag)),
+                "!StrF" => render_tag_strf(_ids, base_dir, &tagged.value)
+                    .context("failed to resolve !StrF")?,
+                "!Id" => render_tag_id(_ids, base_dir, &mut tagged.value)
+                    .context("failed to resolve !Id")?,
+                "!Env" => render_tag_env(_ids, base_dir, &mut tagged.value)
+                    .context("failed to resolve !Env")?,
+                "!File" => render_tag_file(_ids, base_dir, &mut tagged.value)
+                    .context("failed to resolve !File")?,
+                "!Choice" => render_tag_choice(_ids, base_dir, &mut tagged.value)
+                    .context("failed to resolve !Choice")?,
+                "!Confirm" => render_tag_confirm(_ids, base_dir, &mut tagged.value)
+                    .context("failed to resolve !Confirm")?,
+                "!Secret" => render_tag_secret(_ids, base_dir, &mut tagged.value)
+                    .context("failed to resolve !Secret")?,
+                "!Cmd" => render_tag_cmd(_ids, base_dir, &mut tagged.value)
+                    .context("failed to resolve !Cmd")?,
+                tag => match FUNCTIONS.iter().find(|function| function.tag == tag) {
+                    Some(function) => {
+                        render_tag_function(_ids, base_dir, function, &mut tagged.value)
+                            .context(format!("failed to resolve {}", tag))?
+                    }
+                    None => bail!(format!("{} is not a valid tag", tagged.tag)),
+                },
             };
             std::mem::swap(value, &mut new_value);
         }
@@ -36,33 +74,482 @@ pub fn render(_ids: &mut HashMap<String, Value>, value: &mut Value) -> Result<()
     Ok(())
 }
 
-fn render_tag_strf(_ids: &mut HashMap<String, Value>, tag_value: &Value) -> Result<Value> {
+fn render_tag_strf(
+    _ids: &mut HashMap<String, Value>,
+    base_dir: &Path,
+    tag_value: &Value,
+) -> Result<Value> {
     if !tag_value.is_sequence() {
         bail!("StringF needs to be a sequence of Strings",);
     }
     let mut formatted_string = String::new();
     for v in tag_value.as_sequence().unwrap().to_owned().iter_mut() {
-        render(_ids, v)?;
-        if !v.is_string() {
-            bail!("StringF needs to be a sequence of strings",);
-        }
-        formatted_string += v.as_str().unwrap();
+        render(_ids, base_dir, v)?;
+        formatted_string += &super::template::value_to_string(v)
+            .context("StringF needs to be a sequence of strings, numbers or bools")?;
     }
     Ok(Value::String(formatted_string))
 }
 
+/// A function that can be used as tag or in a `{{ }}` template.
+pub struct Function {
+    /// the name in templates
+    pub name: &'static str,
+    pub tag: &'static str,
+    call: fn(&[Value]) -> Result<Value>,
+}
+
+/// All functions of the render engine.
+pub const FUNCTIONS: &[Function] = &[
+    Function {
+        name: "join",
+        tag: "!Join",
+        call: function_join,
+    },
+    Function {
+        name: "split",
+        tag: "!Split",
+        call: function_split,
+    },
+    Function {
+        name: "upper",
+        tag: "!Upper",
+        call: function_upper,
+    },
+    Function {
+        name: "lower",
+        tag: "!Lower",
+        call: function_lower,
+    },
+    Function {
+        name: "replace",
+        tag: "!Replace",
+        call: function_replace,
+    },
+    Function {
+        name: "match",
+        tag: "!Match",
+        call: function_match,
+    },
+    Function {
+        name: "extract",
+        tag: "!Extract",
+        call: function_extract,
+    },
+    Function {
+        name: "base64",
+        tag: "!Base64",
+        call: function_base64,
+    },
+    Function {
+        name: "hex",
+        tag: "!Hex",
+        call: function_hex,
+    },
+    Function {
+        name: "sha256",
+        tag: "!Sha256",
+        call: function_sha256,
+    },
+    Function {
+        name: "path_join",
+        tag: "!PathJoin",
+        call: function_path_join,
+    },
+    Function {
+        name: "dirname",
+        tag: "!Dirname",
+        call: function_dirname,
+    },
+    Function {
+        name: "basename",
+        tag: "!Basename",
+        call: function_basename,
+    },
+    Function {
+        name: "now",
+        tag: "!Now",
+        call: function_now,
+    },
+    Function {
+        name: "str",
+        tag: "!Str",
+        call: function_str,
+    },
+];
+
+/// Calls the function with the name.
+pub fn call_function(name: &str, args: &[Value]) -> Result<Value> {
+    match FUNCTIONS.iter().find(|function| function.name == name) {
+        Some(function) => (function.call)(args).context(format!("{}() failed", name)),
+        None => bail!(format!("{} is not a function", name)),
+    }
+}
+
+/// The arguments of a function tag are the items of a sequence, a single value or nothing.
+fn render_tag_function(
+    _ids: &mut HashMap<String, Value>,
+    base_dir: &Path,
+    function: &Function,
+    tag_value: &mut Value,
+) -> Result<Value> {
+    render(_ids, base_dir, tag_value)?;
+    let args = match tag_value {
+        Value::Sequence(seq) => seq.clone(),
+        Value::Null => Vec::new(),
+        other => vec![other.clone()],
+    };
+    (function.call)(&args)
+}
+
+fn check_args(args: &[Value], min: usize, max: usize) -> Result<()> {
+    if args.len() < min || args.len() > max {
+        match min == max {
+            true => bail!(format!("takes {} arguments but got {}", min, args.len())),
+            false => bail!(format!(
+                "takes {} to {} arguments but got {}",
+                min,
+                max,
+                args.len()
+            )),
+        }
+    }
+    Ok(())
+}
+
+/// Returns the argument as text. Numbers and bools are converted.
+fn text_arg(args: &[Value], index: usize) -> Result<String> {
+    super::template::value_to_string(&args[index]).context(format!(
+        "argument {} is not a string, number or bool",
+        index + 1
+    ))
+}
+
+fn regex_arg(args: &[Value], index: usize) -> Result<regex::Regex> {
+    let pattern = text_arg(args, index)?;
+    regex::Regex::new(&pattern).context(format!("{} is not a valid regex", pattern))
+}
+
+/// `join(list, separator)`
+fn function_join(args: &[Value]) -> Result<Value> {
+    check_args(args, 2, 2)?;
+    let items = args[0]
+        .as_sequence()
+        .context("argument 1 is not a sequence")?
+        .iter()
+        .map(super::template::value_to_string)
+        .collect::<Result<Vec<String>>>()?;
+    Ok(Value::String(items.join(&text_arg(args, 1)?)))
+}
+
+/// `split(text, separator)`
+fn function_split(args: &[Value]) -> Result<Value> {
+    check_args(args, 2, 2)?;
+    let separator = text_arg(args, 1)?;
+    if separator.is_empty() {
+        bail!("the separator is empty");
+    }
+    Ok(Value::Sequence(
+        text_arg(args, 0)?
+            .split(&separator)
+            .map(|part| Value::String(part.to_string()))
+            .collect(),
+    ))
+}
+
+/// `upper(text)`
+fn function_upper(args: &[Value]) -> Result<Value> {
+    check_args(args, 1, 1)?;
+    Ok(Value::String(text_arg(args, 0)?.to_uppercase()))
+}
+
+/// `lower(text)`
+fn function_lower(args: &[Value]) -> Result<Value> {
+    check_args(args, 1, 1)?;
+    Ok(Value::String(text_arg(args, 0)?.to_lowercase()))
+}
+
+/// `replace(text, from, to)`
+fn function_replace(args: &[Value]) -> Result<Value> {
+    check_args(args, 3, 3)?;
+    Ok(Value::String(
+        text_arg(args, 0)?.replace(&text_arg(args, 1)?, &text_arg(args, 2)?),
+    ))
+}
+
+/// `match(text, regex)` is true if the regex matches a part of the text.
+fn function_match(args: &[Value]) -> Result<Value> {
+    check_args(args, 2, 2)?;
+    Ok(Value::Bool(
+        regex_arg(args, 1)?.is_match(&text_arg(args, 0)?),
+    ))
+}
+
+/// `extract(text, regex, group)` returns the first match or its group (a number or name), null if
+/// the regex does not match.
+fn function_extract(args: &[Value]) -> Result<Value> {
+    check_args(args, 2, 3)?;
+    let text = text_arg(args, 0)?;
+    let captures = match regex_arg(args, 1)?.captures(&text) {
+        Some(captures) => captures,
+        None => return Ok(Value::Null),
+    };
+    let group = match args.get(2) {
+        None => captures.get(0),
+        Some(Value::Number(number)) => {
+            let index = number.as_u64().context("group is not a positive number")?;
+            captures.get(index as usize)
+        }
+        Some(Value::String(name)) => captures.name(name),
+        Some(_) => bail!("group is neither a number nor a name"),
+    };
+    Ok(match group {
+        Some(group) => Value::String(group.as_str().to_string()),
+        None => Value::Null,
+    })
+}
+
+/// `base64(text)`
+fn function_base64(args: &[Value]) -> Result<Value> {
+    use base64::Engine;
+    check_args(args, 1, 1)?;
+    Ok(Value::String(
+        base64::engine::general_purpose::STANDARD.encode(text_arg(args, 0)?),
+    ))
+}
+
+/// `hex(text)`
+fn function_hex(args: &[Value]) -> Result<Value> {
+    check_args(args, 1, 1)?;
+    Ok(Value::String(to_hex(text_arg(args, 0)?.as_bytes())))
+}
+
+/// `sha256(text)` returns the hex digest.
+fn function_sha256(args: &[Value]) -> Result<Value> {
+    use sha2::{Digest, Sha256};
+    check_args(args, 1, 1)?;
+    Ok(Value::String(to_hex(&Sha256::digest(
+        text_arg(args, 0)?.as_bytes(),
+    ))))
+}
+
+/// `path_join(path, ...)` where an absolute part replaces the path before it.
+fn function_path_join(args: &[Value]) -> Result<Value> {
+    check_args(args, 1, usize::MAX)?;
+    let mut path = std::path::PathBuf::new();
+    for index in 0..args.len() {
+        path.push(text_arg(args, index)?);
+    }
+    Ok(Value::String(path.to_string_lossy().into_owned()))
+}
+
+/// `dirname(path)`, empty if the path has no parent.
+fn function_dirname(args: &[Value]) -> Result<Value> {
+    check_args(args, 1, 1)?;
+    let path = text_arg(args, 0)?;
+    Ok(Value::String(
+        Path::new(&path)
+            .parent()
+            .map(|parent| parent.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+    ))
+}
+
+/// `basename(path)`, empty if the path ends with `..`.
+fn function_basename(args: &[Value]) -> Result<Value> {
+    check_args(args, 1, 1)?;
+    let path = text_arg(args, 0)?;
+    Ok(Value::String(
+        Path::new(&path)
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+    ))
+}
+
+/// `now(format)` returns the local time in the strftime format, `%Y-%m-%d %H:%M:%S` by default.
+fn function_now(args: &[Value]) -> Result<Value> {
+    use chrono::format::{Item, StrftimeItems};
+    check_args(args, 0, 1)?;
+    let format = match args.first() {
+        Some(_) => text_arg(args, 0)?,
+        None => "%Y-%m-%d %H:%M:%S".to_string(),
+    };
+    if StrftimeItems::new(&format).any(|item| item == Item::Error) {
+        bail!(format!("{} is not a valid date format", format));
+    }
+    Ok(Value::String(
+        chrono::Local::now().format(&format).to_string(),
+    ))
+}
+
+/// `str(value, decimals)` converts a value into a string. Numbers are rounded to the decimals if
+/// they are given.
+fn function_str(args: &[Value]) -> Result<Value> {
+    check_args(args, 1, 2)?;
+    match (&args[0], args.get(1)) {
+        (_, None) => Ok(Value::String(text_arg(args, 0)?)),
+        (Value::Number(number), Some(decimals)) => {
+            let decimals = decimals
+                .as_u64()
+                .context("decimals is not a positive number")?;
+            let number = number.as_f64().context("the number is too large")?;
+            Ok(Value::String(format!("{:.*}", decimals as usize, number)))
+        }
+        (_, Some(_)) => bail!("decimals can only be given for numbers"),
+    }
+}
+
+/// The type of the answer of an `!Input`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum InputType {
+    String,
+    Int,
+    Path,
+    Bool,
+}
+
+/// How the answer of an `!Input` is checked and converted.
+#[derive(Debug)]
+struct InputRules {
+    default: Option<String>,
+    input_type: InputType,
+    /// a regex the whole answer has to match, as written and anchored
+    pattern: Option<(String, regex::Regex)>,
+    allow_empty: bool,
+}
+
+impl Default for InputRules {
+    fn default() -> Self {
+        Self {
+            default: None,
+            input_type: InputType::String,
+            pattern: None,
+            allow_empty: true,
+        }
+    }
+}
+
+/// Parses yes/no answers.
+fn parse_bool(answer: &str) -> Option<bool> {
+    match answer.trim().to_lowercase().as_str() {
+        "y" | "yes" | "true" => Some(true),
+        "n" | "no" | "false" => Some(false),
+        _ => None,
+    }
+}
+
+/// Checks the answer and converts it into the value of the input. The error is shown to the user
+/// before asking again.
+fn parse_answer(answer: &str, rules: &InputRules) -> std::result::Result<Value, String> {
+    let answer = match (&rules.default, answer.is_empty()) {
+        (Some(default), true) => default.as_str(),
+        _ => answer,
+    };
+    if answer.is_empty() {
+        return match rules.allow_empty {
+            true => Ok(Value::String(String::new())),
+            false => Err("An answer is required".to_string()),
+        };
+    }
+    if let Some((text, pattern)) = &rules.pattern {
+        if !pattern.is_match(answer) {
+            return Err(format!("The answer has to match {}", text));
+        }
+    }
+    match rules.input_type {
+        InputType::String => Ok(Value::String(answer.to_string())),
+        InputType::Int => match answer.trim().parse::<i64>() {
+            Ok(number) => Ok(Value::Number(number.into())),
+            Err(_) => Err(format!("{} is not an int", answer)),
+        },
+        InputType::Bool => match parse_bool(answer) {
+            Some(boolean) => Ok(Value::Bool(boolean)),
+            None => Err(format!("{} is not yes or no", answer)),
+        },
+        InputType::Path => match Path::new(answer).try_resolve() {
+            Ok(path) => Ok(Value::String(path.to_string_lossy().into_owned())),
+            Err(error) => Err(format!("{} is not a valid path: {}", answer, error)),
+        },
+    }
+}
+
+/// Prints the prompt and reads one line without the line break.
+fn read_answer(prompt: &str, hidden: bool) -> Result<String> {
+    if hidden {
+        return rpassword::prompt_password(prompt).context("hidden input failed (rpassword)");
+    }
+    print!("{}", prompt);
+    std::io::stdout()
+        .flush()
+        .context("failed to flush stdout")?;
+    let mut input = String::new();
+    let read = std::io::stdin()
+        .read_line(&mut input)
+        .context("failed to read line")?;
+    if read == 0 {
+        bail!("stdin was closed before an answer was given");
+    }
+    // remove linebraks
+    while input.ends_with('\n') || input.ends_with('\r') {
+        input.pop();
+    }
+    Ok(input)
+}
+
+/// Asks until the answer can be parsed. The answer is recorded with the prompt or registered as
+/// a secret if it is hidden.
+fn ask(
+    question: &str,
+    prompt: &str,
+    hidden: bool,
+    parse: impl Fn(&str) -> std::result::Result<Value, String>,
+) -> Result<Value> {
+    let value = loop {
+        let answer = read_answer(question, hidden)?;
+        match parse(&answer) {
+            Ok(value) => break value,
+            Err(message) => eprintln!("{}", message),
+        }
+    };
+    let answer = match &value {
+        Value::String(answer) => answer.clone(),
+        other => super::template::value_to_string(other)?,
+    };
+    if hidden {
+        mask::register(&answer);
+    } else {
+        let prompt_text = prompt.trim().trim_end_matches(':').to_string();
+        ANSWERS.lock().unwrap().push((prompt_text, answer));
+    }
+    Ok(value)
+}
+
+/// Converts a scalar default into the text that would be typed.
+fn default_to_string(value: Value) -> Result<String> {
+    match value {
+        Value::String(_) | Value::Number(_) | Value::Bool(_) => {
+            super::template::value_to_string(&value)
+        }
+        _ => bail!("default is not of type string, number or bool"),
+    }
+}
+
+#[allow(clippy::get_first)]
 fn render_tag_input(
     _ids: &mut HashMap<String, Value>,
+    base_dir: &Path,
     tag_value: &mut Value,
     hidden: bool,
 ) -> Result<Value> {
-    render(_ids, tag_value)?;
+    render(_ids, base_dir, tag_value)?;
     // check if the input type is correct
     if !tag_value.is_string() && !tag_value.is_sequence() && !tag_value.is_mapping() {
         bail!("Input prompt is not a valid string, sequence or map");
     }
-    let (prompt, default): (String, Option<String>) = match tag_value {
-        Value::String(prompt) => (prompt.to_owned(), None),
+    let mut rules = InputRules::default();
+    let prompt = match tag_value {
+        Value::String(prompt) => prompt.to_owned(),
         Value::Sequence(seq) => {
             // check if length is correct
             if seq.len() != 2 && seq.len() != 1 {
@@ -79,15 +566,10 @@ fn render_tag_input(
             if seq.len() == 2 && !seq.get(1).unwrap().is_string() {
                 bail!("Input default value is not a valid string");
             }
-            // return
-            (
-                seq[0].as_str().unwrap().to_owned(),
-                if seq.len() == 2 {
-                    Some(seq[1].as_str().unwrap().to_owned())
-                } else {
-                    None
-                },
-            )
+            rules.default = seq
+                .get(1)
+                .map(|default| default.as_str().unwrap().to_owned());
+            seq[0].as_str().unwrap().to_owned()
         }
         Value::Mapping(map) => {
             // get and check prompt
@@ -99,49 +581,360 @@ fn render_tag_input(
                 None => bail!("prompt was not provided in !Input"),
             };
             // get and check default if given
-            let default = match get_entry(map, Value::String(String::from("default"))) {
-                Some(value) => match value {
-                    Value::String(text) => Some(text),
-                    _ => bail!("default is not of type string"),
-                },
+            rules.default = match get_entry(map, Value::String(String::from("default"))) {
+                Some(value) => Some(default_to_string(value)?),
                 _ => None,
             };
-            // return
-            (prompt, default)
+            rules.input_type = match get_entry(map, "type".into()) {
+                Some(Value::String(name)) => match name.as_str() {
+                    "string" => InputType::String,
+                    "int" => InputType::Int,
+                    "path" => InputType::Path,
+                    "bool" => InputType::Bool,
+                    _ => bail!(format!(
+                        "type {} is not one of string, int, path and bool",
+                        name
+                    )),
+                },
+                Some(_) => bail!("type is not of type string"),
+                None => InputType::String,
+            };
+            rules.pattern = match get_entry(map, "pattern".into()) {
+                Some(Value::String(pattern)) => {
+                    let regex = regex::Regex::new(&format!("^(?:{})$", pattern))
+                        .context(format!("pattern {} is not a valid regex", pattern))?;
+                    Some((pattern, regex))
+                }
+                Some(_) => bail!("pattern is not of type string"),
+                None => None,
+            };
+            rules.allow_empty = match get_entry(map, "allow_empty".into()) {
+                Some(Value::Bool(allow_empty)) => allow_empty,
+                Some(_) => bail!("allow_empty is not of type bool"),
+                None => true,
+            };
+            prompt
         }
         _ => bail!("Input prompt is not a valid string, sequence or map"),
     };
-    // print the prompt
-    if !hidden {
-        print!("{}", prompt);
-    }
-    // get input
-    let mut input = match hidden {
-        true => rpassword::prompt_password(prompt).context("hidden input failed (rpassword)")?,
-        false => {
-            std::io::stdout()
-                .flush()
-                .context("failed to flush stdout")?;
-            let mut input_string = String::new();
-            std::io::stdin()
-                .read_line(&mut input_string)
-                .context("failed to read line")?;
-            input_string
+    ask(&prompt, &prompt, hidden, |answer| {
+        parse_answer(answer, &rules)
+    })
+}
+
+/// Returns the option that was chosen by its number or text.
+fn parse_choice(
+    answer: &str,
+    options: &[Value],
+    default: &Option<Value>,
+) -> std::result::Result<Value, String> {
+    let answer = answer.trim();
+    if answer.is_empty() {
+        return default
+            .clone()
+            .ok_or_else(|| "Choose one of the options".to_string());
+    }
+    if let Ok(number) = answer.parse::<usize>() {
+        if number >= 1 && number <= options.len() {
+            return Ok(options[number - 1].clone());
         }
+    }
+    options
+        .iter()
+        .find(|option| super::template::value_to_string(option).ok().as_deref() == Some(answer))
+        .cloned()
+        .ok_or_else(|| format!("{} is not one of the options", answer))
+}
+
+fn render_tag_choice(
+    _ids: &mut HashMap<String, Value>,
+    base_dir: &Path,
+    tag_value: &mut Value,
+) -> Result<Value> {
+    render(_ids, base_dir, tag_value)?;
+    let map = tag_value
+        .as_mapping()
+        .context("!Choice value needs to be a map")?;
+    let prompt = match get_entry(map, "prompt".into()) {
+        Some(Value::String(prompt)) => prompt,
+        Some(_) => bail!("prompt is not of type string"),
+        None => bail!("prompt was not provided in !Choice"),
     };
-    // remove linebraks
-    while input.ends_with('\n') || input.ends_with('\r') {
-        input.remove(input.len() - 1);
+    let options = match get_entry(map, "options".into()) {
+        Some(Value::Sequence(options)) if !options.is_empty() => options,
+        Some(_) => bail!("options is not a sequence with at least one option"),
+        None => bail!("options were not provided in !Choice"),
+    };
+    let mut texts = Vec::new();
+    for option in &options {
+        texts.push(super::template::value_to_string(option).context(format!(
+            "option {:?} is not a string, number or bool",
+            option
+        ))?);
     }
-    // replace with default if default and input is empty
-    if default.is_some() && input.is_empty() {
-        input = default.unwrap();
+    let default = match get_entry(map, "default".into()) {
+        Some(default) => Some(
+            parse_choice(&default_to_string(default)?, &options, &None)
+                .map_err(|message| anyhow::anyhow!(message))
+                .context("default is not one of the options")?,
+        ),
+        None => None,
+    };
+
+    println!("{}", prompt);
+    for (index, text) in texts.iter().enumerate() {
+        println!("  {}) {}", index + 1, text);
+    }
+    let question = match &default {
+        Some(default) => format!(
+            "Choose 1-{} [{}]: ",
+            options.len(),
+            super::template::value_to_string(default)?
+        ),
+        None => format!("Choose 1-{}: ", options.len()),
+    };
+    ask(&question, &prompt, false, |answer| {
+        parse_choice(answer, &options, &default)
+    })
+}
+
+fn render_tag_confirm(
+    _ids: &mut HashMap<String, Value>,
+    base_dir: &Path,
+    tag_value: &mut Value,
+) -> Result<Value> {
+    render(_ids, base_dir, tag_value)?;
+    let (prompt, default) = match tag_value {
+        Value::String(prompt) => (prompt.to_owned(), None),
+        Value::Mapping(map) => {
+            let prompt = match get_entry(map, "prompt".into()) {
+                Some(Value::String(prompt)) => prompt,
+                Some(_) => bail!("prompt is not of type string"),
+                None => bail!("prompt was not provided in !Confirm"),
+            };
+            let default = match get_entry(map, "default".into()) {
+                Some(Value::Bool(default)) => Some(default),
+                Some(_) => bail!("default is not of type bool"),
+                None => None,
+            };
+            (prompt, default)
+        }
+        _ => bail!("!Confirm value needs to be a string or map"),
+    };
+    let hint = match default {
+        Some(true) => "[Y/n]",
+        Some(false) => "[y/N]",
+        None => "[y/n]",
+    };
+    let question = format!("{} {} ", prompt.trim_end(), hint);
+    ask(&question, &prompt, false, |answer| {
+        match (parse_bool(answer), default, answer.trim().is_empty()) {
+            (Some(answer), _, _) => Ok(Value::Bool(answer)),
+            (None, Some(default), true) => Ok(Value::Bool(default)),
+            _ => Err("Answer yes or no".to_string()),
+        }
+    })
+}
+
+fn render_tag_env(
+    _ids: &mut HashMap<String, Value>,
+    base_dir: &Path,
+    tag_value: &mut Value,
+) -> Result<Value> {
+    render(_ids, base_dir, tag_value)?;
+    let (name, default) = match tag_value {
+        Value::String(name) => (name.to_owned(), None),
+        Value::Sequence(seq) => {
+            if seq.len() != 2 && seq.len() != 1 {
+                bail!(format!("!Env takes 1 or 2 arguments but got {}", seq.len()));
+            }
+            let name = seq[0]
+                .as_str()
+                .context("!Env variable name is not a string")?
+                .to_owned();
+            (name, seq.get(1).cloned())
+        }
+        _ => bail!("!Env value needs to be a string or sequence"),
+    };
+    match std::env::var(&name) {
+        Ok(value) => Ok(Value::String(value)),
+        Err(std::env::VarError::NotUnicode(_)) => {
+            bail!(format!(
+                "environment variable {} is not valid unicode",
+                name
+            ))
+        }
+        Err(std::env::VarError::NotPresent) => match default {
+            Some(default) => Ok(default),
+            None => bail!(format!(
+                "environment variable {} is not set and has no default",
+                name
+            )),
+        },
     }
-    // return input
-    Ok(Value::String(input))
 }
 
-fn render_tag_id(_ids: &mut HashMap<String, Value>, tag_value: &mut Value) -> Result<Value> {
+fn render_tag_file(
+    _ids: &mut HashMap<String, Value>,
+    base_dir: &Path,
+    tag_value: &mut Value,
+) -> Result<Value> {
+    render(_ids, base_dir, tag_value)?;
+    let (path, format) = match tag_value {
+        Value::String(path) => (path.to_owned(), None),
+        Value::Mapping(map) => {
+            let path = match get_entry(map, "path".into()) {
+                Some(Value::String(path)) => path,
+                Some(_) => bail!("path is not of type string"),
+                None => bail!("path was not provided in !File"),
+            };
+            let format = match get_entry(map, "format".into()) {
+                Some(Value::String(format)) => Some(format),
+                Some(_) => bail!("format is not of type string"),
+                None => None,
+            };
+            (path, format)
+        }
+        _ => bail!("!File value needs to be a string or map"),
+    };
+    let path = Path::new(&path)
+        .try_resolve_in(base_dir)
+        .context(format!("could not resolve {}", path))?
+        .into_owned();
+    let mut content =
+        std::fs::read_to_string(&path).context(format!("could not read {:?}", path))?;
+
+    match format.as_deref() {
+        None | Some("text") => {
+            while content.ends_with('\n') || content.ends_with('\r') {
+                content.pop();
+            }
+            Ok(Value::String(content))
+        }
+        Some("yaml") => {
+            let mut value: Value =
+                serde_yaml::from_str(&content).context(format!("{:?} is not valid yaml", path))?;
+            let path = path
+                .canonicalize()
+                .context(format!("Could not resolve the path of {:?}", path))?;
+            if let Some(cycle) = FILE_STACK.with_borrow(|stack| {
+                stack.contains(&path).then(|| {
+                    stack
+                        .iter()
+                        .skip_while(|file| **file != path)
+                        .chain(std::iter::once(&path))
+                        .map(|file| file.display().to_string())
+                        .collect::<Vec<String>>()
+                })
+            }) {
+                bail!(format!("!File cycle: {}", cycle.join(" -> ")));
+            }
+            let dir = path.parent().unwrap_or(base_dir);
+            FILE_STACK.with_borrow_mut(|stack| stack.push(path.clone()));
+            let result = render(_ids, dir, &mut value);
+            FILE_STACK.with_borrow_mut(|stack| stack.pop());
+            result?;
+            Ok(value)
+        }
+        Some("json") => {
+            serde_json::from_str(&content).context(format!("{:?} is not valid json", path))
+        }
+        Some(format) => bail!(format!(
+            "format {} is not one of text, yaml and json",
+            format
+        )),
+    }
+}
+
+fn render_tag_secret(
+    _ids: &mut HashMap<String, Value>,
+    base_dir: &Path,
+    tag_value: &mut Value,
+) -> Result<Value> {
+    render(_ids, base_dir, tag_value)?;
+    let name = tag_value
+        .as_str()
+        .context("!Secret value needs to be the name of a secret")?;
+    Ok(Value::String(secrets::lookup(name)?))
+}
+
+fn render_tag_cmd(
+    _ids: &mut HashMap<String, Value>,
+    base_dir: &Path,
+    tag_value: &mut Value,
+) -> Result<Value> {
+    render(_ids, base_dir, tag_value)?;
+    let default_shell = match cfg!(windows) {
+        true => "cmd",
+        false => "sh",
+    };
+    let (command, shell, work_dir, trim) = match tag_value {
+        Value::String(command) => (command.to_owned(), default_shell.to_string(), None, true),
+        Value::Mapping(map) => {
+            let command = match get_entry(map, "command".into()) {
+                Some(Value::String(command)) => command,
+                Some(_) => bail!("command is not of type string"),
+                None => bail!("command was not provided in !Cmd"),
+            };
+            let shell = match get_entry(map, "shell".into()) {
+                Some(Value::String(shell)) => shell,
+                Some(_) => bail!("shell is not of type string"),
+                None => default_shell.to_string(),
+            };
+            let work_dir = match get_entry(map, "work_dir".into()) {
+                Some(Value::String(work_dir)) => Some(work_dir),
+                Some(_) => bail!("work_dir is not of type string"),
+                None => None,
+            };
+            let trim = match get_entry(map, "trim".into()) {
+                Some(Value::Bool(trim)) => trim,
+                Some(_) => bail!("trim is not of type bool"),
+                None => true,
+            };
+            (command, shell, work_dir, trim)
+        }
+        _ => bail!("!Cmd value needs to be a string or map"),
+    };
+    let work_dir = match work_dir {
+        Some(work_dir) => Path::new(&work_dir)
+            .try_resolve_in(base_dir)
+            .context(format!("could not resolve work_dir {}", work_dir))?
+            .into_owned(),
+        None => base_dir.to_path_buf(),
+    };
+
+    let flag = match shell.as_str() {
+        "cmd" | "cmd.exe" => "/C",
+        _ => "-c",
+    };
+    let output = std::process::Command::new(&shell)
+        .arg(flag)
+        .arg(&command)
+        .current_dir(&work_dir)
+        .stdin(std::process::Stdio::null())
+        .output()
+        .context(format!("could not run `{}` with {}", command, shell))?;
+    if !output.status.success() {
+        bail!(format!(
+            "`{}` failed with {}: {}",
+            command,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim_end()
+        ));
+    }
+    let stdout = String::from_utf8(output.stdout)
+        .context(format!("output of `{}` is not valid utf-8", command))?;
+    match trim {
+        true => Ok(Value::String(stdout.trim().to_string())),
+        false => Ok(Value::String(stdout)),
+    }
+}
+
+fn render_tag_id(
+    _ids: &mut HashMap<String, Value>,
+    base_dir: &Path,
+    tag_value: &mut Value,
+) -> Result<Value> {
     let id = match &tag_value {
         Value::Mapping(content_map) => match get_entry(content_map, "id".into()) {
             Some(id_value) => id_value
@@ -177,7 +970,7 @@ fn render_tag_id(_ids: &mut HashMap<String, Value>, tag_value: &mut Value) -> Re
     };
 
     if !_ids.contains_key(&id) {
-        render(_ids, &mut id_value)?;
+        render(_ids, base_dir, &mut id_value)?;
         _ids.insert(id.clone(), id_value);
     }
 
@@ -185,13 +978,19 @@ fn render_tag_id(_ids: &mut HashMap<String, Value>, tag_value: &mut Value) -> Re
 }
 
 #[cfg(test)]
+#[allow(clippy::needless_borrow, clippy::iter_nth)]
 mod tests {
     #[test]
     fn str_f_test() {
         use super::render;
         let content = "!StrF ['test', 'testa']";
         let mut value: serde_yaml::Value = serde_yaml::from_str(&content).unwrap();
-        render(&mut std::collections::HashMap::new(), &mut value).unwrap();
+        render(
+            &mut std::collections::HashMap::new(),
+            std::path::Path::new("."),
+            &mut value,
+        )
+        .unwrap();
         assert_eq!("testtesta", value.as_str().unwrap());
     }
 
@@ -204,7 +1003,12 @@ mod tests {
         key2: !Id ['id', 'Second Value']
         ";
         let mut value: serde_yaml::Value = serde_yaml::from_str(&content).unwrap();
-        render(&mut std::collections::HashMap::new(), &mut value).unwrap();
+        render(
+            &mut std::collections::HashMap::new(),
+            std::path::Path::new("."),
+            &mut value,
+        )
+        .unwrap();
         // assert that at key2 the first value for the id `id` is used
         assert_eq!(
             "First Value",
@@ -224,7 +1028,12 @@ mod tests {
         key2: !Id {id: 'id', value: 'Second Value'}
         ";
         let mut value: serde_yaml::Value = serde_yaml::from_str(&content).unwrap();
-        render(&mut std::collections::HashMap::new(), &mut value).unwrap();
+        render(
+            &mut std::collections::HashMap::new(),
+            std::path::Path::new("."),
+            &mut value,
+        )
+        .unwrap();
         // assert that at key2 the first value for the id `id` is used
         assert_eq!(
             "First Value",
@@ -245,7 +1054,12 @@ mod tests {
         key2: !Id {id: 'id', value: 'Second Value'}
         ";
         let mut value: serde_yaml::Value = serde_yaml::from_str(&content).unwrap();
-        render(&mut std::collections::HashMap::new(), &mut value).unwrap();
+        render(
+            &mut std::collections::HashMap::new(),
+            std::path::Path::new("."),
+            &mut value,
+        )
+        .unwrap();
         // assert that at key2 the first value for the id `id` is used
         assert_eq!(
             "First Value",
@@ -265,7 +1079,12 @@ mod tests {
         key2: !Id ['id', 'Second Value']
         ";
         let mut value: serde_yaml::Value = serde_yaml::from_str(&content).unwrap();
-        render(&mut std::collections::HashMap::new(), &mut value).unwrap();
+        render(
+            &mut std::collections::HashMap::new(),
+            std::path::Path::new("."),
+            &mut value,
+        )
+        .unwrap();
         // assert that at key2 the first value for the id `id` is used
         assert_eq!(
             "First Value",
@@ -276,6 +1095,195 @@ mod tests {
         );
     }
 
+    #[test]
+    fn env_test() {
+        use super::super::get_entry;
+        use super::render;
+        std::env::set_var("WORKFLOWO_ENV_TEST", "from env");
+        let content = "
+        key1: !Env WORKFLOWO_ENV_TEST
+        key2: !Env [WORKFLOWO_ENV_TEST, 'default']
+        key3: !Env [WORKFLOWO_ENV_TEST_MISSING, 'default']
+        ";
+        let mut value: serde_yaml::Value = serde_yaml::from_str(content).unwrap();
+        render(
+            &mut std::collections::HashMap::new(),
+            std::path::Path::new("."),
+            &mut value,
+        )
+        .unwrap();
+        let map = value.as_mapping().unwrap();
+        assert_eq!(get_entry(map, "key1".into()).unwrap(), "from env");
+        assert_eq!(get_entry(map, "key2".into()).unwrap(), "from env");
+        assert_eq!(get_entry(map, "key3".into()).unwrap(), "default");
+
+        let mut value: serde_yaml::Value =
+            serde_yaml::from_str("!Env WORKFLOWO_ENV_TEST_MISSING").unwrap();
+        let error = render(
+            &mut std::collections::HashMap::new(),
+            std::path::Path::new("."),
+            &mut value,
+        )
+        .unwrap_err();
+        assert!(format!("{:#}", error).contains("WORKFLOWO_ENV_TEST_MISSING is not set"));
+    }
+
+    #[test]
+    fn file_test() {
+        use super::super::get_entry;
+        use super::render;
+        let dir = std::env::temp_dir().join(format!("workflowo-file-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("password"), "secret\n").unwrap();
+        std::fs::write(dir.join("hosts.yaml"), "- a\n- b\n").unwrap();
+        std::fs::write(dir.join("hosts.json"), r#"{"hosts": ["c"]}"#).unwrap();
+        let content = "
+        password: !File password
+        yaml: !File {path: hosts.yaml, format: yaml}
+        json: !File {path: hosts.json, format: json}
+        ";
+        let mut value: serde_yaml::Value = serde_yaml::from_str(content).unwrap();
+        render(&mut std::collections::HashMap::new(), &dir, &mut value).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let map = value.as_mapping().unwrap();
+        assert_eq!(get_entry(map, "password".into()).unwrap(), "secret");
+        assert_eq!(
+            get_entry(map, "yaml".into()).unwrap(),
+            serde_yaml::from_str::<serde_yaml::Value>("[a, b]").unwrap()
+        );
+        assert_eq!(
+            get_entry(map, "json".into()).unwrap(),
+            serde_yaml::from_str::<serde_yaml::Value>("hosts: [c]").unwrap()
+        );
+    }
+
+    #[test]
+    fn file_cycle_test() {
+        use super::render;
+        let dir = std::env::temp_dir().join(format!("workflowo-file-cycle-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("self.yaml"),
+            "!File {path: self.yaml, format: yaml}",
+        )
+        .unwrap();
+        std::fs::write(dir.join("a.yaml"), "b: !File {path: b.yaml, format: yaml}").unwrap();
+        std::fs::write(dir.join("b.yaml"), "- !File {path: a.yaml, format: yaml}").unwrap();
+        // the same file may be read twice if it is not part of a cycle
+        std::fs::write(dir.join("c.yaml"), "c").unwrap();
+        let content = "[!File {path: c.yaml, format: yaml}, !File {path: c.yaml, format: yaml}]";
+        let mut value: serde_yaml::Value = serde_yaml::from_str(content).unwrap();
+        render(&mut std::collections::HashMap::new(), &dir, &mut value).unwrap();
+
+        for file in ["self.yaml", "a.yaml"] {
+            let content = format!("!File {{path: {}, format: yaml}}", file);
+            let mut value: serde_yaml::Value = serde_yaml::from_str(&content).unwrap();
+            let error = render(&mut std::collections::HashMap::new(), &dir, &mut value)
+                .err()
+                .unwrap();
+            assert!(
+                format!("{:#}", error).contains("!File cycle"),
+                "{:#}",
+                error
+            );
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn cmd_test() {
+        use super::super::get_entry;
+        use super::render;
+        let content = "
+        trimmed: !Cmd 'echo 1.2.3'
+        untrimmed: !Cmd {command: 'echo \"$0\"', shell: bash, work_dir: '/', trim: false}
+        ";
+        let mut value: serde_yaml::Value = serde_yaml::from_str(content).unwrap();
+        render(
+            &mut std::collections::HashMap::new(),
+            std::path::Path::new("."),
+            &mut value,
+        )
+        .unwrap();
+        let map = value.as_mapping().unwrap();
+        assert_eq!(get_entry(map, "trimmed".into()).unwrap(), "1.2.3");
+        assert_eq!(get_entry(map, "untrimmed".into()).unwrap(), "bash\n");
+
+        let mut value: serde_yaml::Value =
+            serde_yaml::from_str("!Cmd 'echo broken >&2; exit 3'").unwrap();
+        let error = render(
+            &mut std::collections::HashMap::new(),
+            std::path::Path::new("."),
+            &mut value,
+        )
+        .unwrap_err();
+        assert!(format!("{:#}", error).contains("broken"));
+    }
+
+    #[test]
+    fn parse_answer_test() {
+        use super::{parse_answer, InputRules, InputType};
+        use serde_yaml::Value;
+
+        let rules = InputRules {
+            default: Some("3".to_string()),
+            input_type: InputType::Int,
+            ..Default::default()
+        };
+        assert_eq!(parse_answer("", &rules), Ok(Value::Number(3.into())));
+        assert_eq!(parse_answer("12", &rules), Ok(Value::Number(12.into())));
+        assert!(parse_answer("twelve", &rules).is_err());
+
+        let rules = InputRules {
+            pattern: Some((
+                "v[0-9]+".to_string(),
+                regex::Regex::new("^(?:v[0-9]+)$").unwrap(),
+            )),
+            allow_empty: false,
+            ..Default::default()
+        };
+        assert!(parse_answer("", &rules).is_err());
+        assert!(parse_answer("v1x", &rules).is_err());
+        assert_eq!(
+            parse_answer("v1", &rules),
+            Ok(Value::String("v1".to_string()))
+        );
+
+        let rules = InputRules {
+            input_type: InputType::Bool,
+            ..Default::default()
+        };
+        assert_eq!(parse_answer("Yes", &rules), Ok(Value::Bool(true)));
+        assert_eq!(parse_answer("n", &rules), Ok(Value::Bool(false)));
+        assert!(parse_answer("maybe", &rules).is_err());
+    }
+
+    #[test]
+    fn parse_choice_test() {
+        use super::parse_choice;
+        use serde_yaml::Value;
+
+        let options: Vec<Value> = serde_yaml::from_str("[staging, prod, 3]").unwrap();
+        let default = Some(Value::String("staging".to_string()));
+        assert_eq!(parse_choice("", &options, &default), Ok(options[0].clone()));
+        assert_eq!(
+            parse_choice("2", &options, &default),
+            Ok(options[1].clone())
+        );
+        assert_eq!(
+            parse_choice("prod", &options, &None),
+            Ok(options[1].clone())
+        );
+        assert_eq!(
+            parse_choice("3", &options, &None),
+            Ok(Value::Number(3.into()))
+        );
+        assert!(parse_choice("4", &options, &None).is_err());
+        assert!(parse_choice("", &options, &None).is_err());
+    }
+
     #[test]
     fn render_strf_nested_test() {
         use super::super::get_entry;
@@ -289,7 +1297,12 @@ mod tests {
                 - !StrF ['test', 'testa']
         ";
         let mut value: serde_yaml::Value = serde_yaml::from_str(&content).unwrap();
-        render(&mut std::collections::HashMap::new(), &mut value).unwrap();
+        render(
+            &mut std::collections::HashMap::new(),
+            std::path::Path::new("."),
+            &mut value,
+        )
+        .unwrap();
 
         assert!(get_entry(&value.as_mapping().unwrap(), "key1".into())
             .unwrap()
@@ -319,4 +1332,204 @@ mod tests {
         .unwrap()
         .is_string())
     }
+
+    /// Calls the function with the arguments written as yaml sequence.
+    fn call(name: &str, args: &str) -> anyhow::Result<serde_yaml::Value> {
+        let args: Vec<serde_yaml::Value> = serde_yaml::from_str(args).unwrap();
+        super::call_function(name, &args)
+    }
+
+    fn string(text: &str) -> serde_yaml::Value {
+        serde_yaml::Value::String(text.to_string())
+    }
+
+    #[test]
+    fn strf_numbers_and_bools_test() {
+        use super::render;
+        let mut value: serde_yaml::Value =
+            serde_yaml::from_str("!StrF ['v', 1, '.', 2.5, '-', true]").unwrap();
+        render(
+            &mut std::collections::HashMap::new(),
+            std::path::Path::new("."),
+            &mut value,
+        )
+        .unwrap();
+        assert_eq!(value, string("v1.2.5-true"));
+
+        let mut value: serde_yaml::Value = serde_yaml::from_str("!StrF ['v', [1]]").unwrap();
+        assert!(render(
+            &mut std::collections::HashMap::new(),
+            std::path::Path::new("."),
+            &mut value,
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn function_join_split_test() {
+        assert_eq!(
+            call("join", "[[a, b, 3], ', ']").unwrap(),
+            string("a, b, 3")
+        );
+        assert_eq!(call("join", "[[], '-']").unwrap(), string(""));
+        assert!(call("join", "[a, ', ']").is_err());
+        assert!(call("join", "[[a]]").is_err());
+        assert_eq!(
+            call("split", "['a,b,,c', ',']").unwrap(),
+            serde_yaml::from_str::<serde_yaml::Value>("[a, b, '', c]").unwrap()
+        );
+        assert!(call("split", "[abc, '']").is_err());
+    }
+
+    #[test]
+    fn function_text_test() {
+        assert_eq!(call("upper", "[MixEd]").unwrap(), string("MIXED"));
+        assert_eq!(call("lower", "[MixEd]").unwrap(), string("mixed"));
+        assert_eq!(call("upper", "[true]").unwrap(), string("TRUE"));
+        assert!(call("upper", "[a, b]").is_err());
+        assert!(call("lower", "[[a]]").is_err());
+        assert_eq!(
+            call("replace", "[a-b-c, '-', '_']").unwrap(),
+            string("a_b_c")
+        );
+        assert_eq!(call("replace", "[1.2.3, '.', '']").unwrap(), string("123"));
+    }
+
+    #[test]
+    fn function_regex_test() {
+        use serde_yaml::Value;
+        assert_eq!(
+            call("match", "['v1.2.3', '^v[0-9]+']").unwrap(),
+            Value::Bool(true)
+        );
+        assert_eq!(
+            call("match", "['1.2.3', '^v[0-9]+']").unwrap(),
+            Value::Bool(false)
+        );
+        assert!(call("match", "[a, '(']").is_err());
+        assert_eq!(
+            call("extract", "['release-1.2.3', '[0-9.]+']").unwrap(),
+            string("1.2.3")
+        );
+        assert_eq!(
+            call("extract", "['release-1.2.3', '([0-9]+)\\.([0-9]+)', 2]").unwrap(),
+            string("2")
+        );
+        assert_eq!(
+            call("extract", "['release-1.2.3', '(?P<major>[0-9]+)', major]").unwrap(),
+            string("1")
+        );
+        assert_eq!(call("extract", "[release, '[0-9]+']").unwrap(), Value::Null);
+        assert_eq!(
+            call("extract", "[release, 'r(x)?', 1]").unwrap(),
+            Value::Null
+        );
+        assert!(call("extract", "[release, 'r', -1]").is_err());
+    }
+
+    #[test]
+    fn function_encoding_test() {
+        assert_eq!(
+            call("base64", "['hello world']").unwrap(),
+            string("aGVsbG8gd29ybGQ=")
+        );
+        assert_eq!(call("hex", "[Hi!]").unwrap(), string("486921"));
+        assert_eq!(
+            call("sha256", "[abc]").unwrap(),
+            string("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad")
+        );
+        assert_eq!(
+            call("sha256", "['']").unwrap(),
+            string("e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855")
+        );
+    }
+
+    #[test]
+    fn function_path_test() {
+        assert_eq!(
+            call("path_join", "[/srv, app, config.yaml]").unwrap(),
+            string("/srv/app/config.yaml")
+        );
+        assert_eq!(call("path_join", "[/srv, /etc]").unwrap(), string("/etc"));
+        assert!(call("path_join", "[]").is_err());
+        assert_eq!(
+            call("dirname", "[/srv/app/config.yaml]").unwrap(),
+            string("/srv/app")
+        );
+        assert_eq!(call("dirname", "[config.yaml]").unwrap(), string(""));
+        assert_eq!(
+            call("basename", "[/srv/app/config.yaml]").unwrap(),
+            string("config.yaml")
+        );
+        assert_eq!(call("basename", "[/srv/app/]").unwrap(), string("app"));
+    }
+
+    #[test]
+    fn function_now_test() {
+        let year = call("now", "['%Y']").unwrap();
+        assert_eq!(year.as_str().unwrap().len(), 4);
+        let default = call("now", "[]").unwrap();
+        assert!(chrono::NaiveDateTime::parse_from_str(
+            default.as_str().unwrap(),
+            "%Y-%m-%d %H:%M:%S"
+        )
+        .is_ok());
+        assert!(call("now", "['%Q']").is_err());
+    }
+
+    #[test]
+    fn function_str_test() {
+        assert_eq!(call("str", "[42]").unwrap(), string("42"));
+        assert_eq!(call("str", "[2.5]").unwrap(), string("2.5"));
+        assert_eq!(call("str", "[false]").unwrap(), string("false"));
+        assert_eq!(call("str", "[3.14159, 2]").unwrap(), string("3.14"));
+        assert_eq!(call("str", "[7, 1]").unwrap(), string("7.0"));
+        assert!(call("str", "[text, 1]").is_err());
+        assert!(call("str", "[[1]]").is_err());
+        assert!(call("unknown", "[]").is_err());
+    }
+
+    #[test]
+    fn function_tag_test() {
+        use super::super::get_entry;
+        use super::render;
+        let content = "
+        joined: !Join [[a, b], '+']
+        upper: !Upper abc
+        nested: !Upper [!Join [[a, b], '-']]
+        path: !PathJoin [/srv, !Lower APP]
+        now: !Now '%Y'
+        number: !Str [1.5, 2]
+        ";
+        let mut value: serde_yaml::Value = serde_yaml::from_str(content).unwrap();
+        render(
+            &mut std::collections::HashMap::new(),
+            std::path::Path::new("."),
+            &mut value,
+        )
+        .unwrap();
+        let map = value.as_mapping().unwrap();
+        assert_eq!(get_entry(map, "joined".into()).unwrap(), "a+b");
+        assert_eq!(get_entry(map, "upper".into()).unwrap(), "ABC");
+        assert_eq!(get_entry(map, "nested".into()).unwrap(), "A-B");
+        assert_eq!(get_entry(map, "path".into()).unwrap(), "/srv/app");
+        assert_eq!(
+            get_entry(map, "now".into())
+                .unwrap()
+                .as_str()
+                .unwrap()
+                .len(),
+            4
+        );
+        assert_eq!(get_entry(map, "number".into()).unwrap(), "1.50");
+
+        let mut value: serde_yaml::Value = serde_yaml::from_str("!Upper [a, b]").unwrap();
+        let error = render(
+            &mut std::collections::HashMap::new(),
+            std::path::Path::new("."),
+            &mut value,
+        )
+        .unwrap_err();
+        assert!(format!("{:#}", error).contains("!Upper"), "{:#}", error);
+    }
 }
@@ -0,0 +1,347 @@
+use super::render;
+use anyhow::{bail, Context, Result};
+use serde_yaml::{Number, Value};
+use std::collections::HashMap;
+
+/// Replaces all `{{ name }}` placeholders in the string values of `value` recursively.
+///
+/// Placeholders of unknown variables are left untouched.
+pub fn substitute(value: &mut Value, vars: &HashMap<String, Value>) -> Result<()> {
+    match value {
+        Value::String(text) => {
+            let mut new_value = render_str(text, vars)?;
+            std::mem::swap(value, &mut new_value);
+        }
+        Value::Mapping(map) => {
+            for map_value in map.values_mut() {
+                substitute(map_value, vars)?;
+            }
+        }
+        Value::Sequence(seq) => {
+            for item in seq {
+                substitute(item, vars)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Renders the placeholders of a single string.
+///
+/// If the whole string is one placeholder its value is returned as it is,
+/// so numbers, bools and lists keep their type.
+pub fn render_str(text: &str, vars: &HashMap<String, Value>) -> Result<Value> {
+    let placeholders = find_placeholders(text);
+    let whole = matches!(placeholders.as_slice(), [(0, end, _)] if *end == text.len());
+
+    let mut rendered = String::new();
+    let mut last_end = 0;
+    for (start, end, expression) in placeholders {
+        rendered += &text[last_end..start];
+        let placeholder = &text[start..end];
+        match evaluate(&expression, vars).context(format!("could not render {}", placeholder))? {
+            Some(value) if whole => return Ok(value),
+            Some(value) => {
+                rendered +=
+                    &value_to_string(&value).context(format!("could not insert {}", placeholder))?
+            }
+            None => rendered += placeholder,
+        }
+        last_end = end;
+    }
+    rendered += &text[last_end..];
+    Ok(Value::String(rendered))
+}
+
+/// The content of a `{{ }}` placeholder.
+#[derive(Debug, Clone, PartialEq)]
+enum Expression {
+    Literal(Value),
+    Variable(String),
+    /// a function of the render engine with its arguments
+    Call(String, Vec<Expression>),
+}
+
+/// Evaluates the expression. `None` if it uses an unknown variable, the placeholder is then left
+/// untouched.
+fn evaluate(expression: &Expression, vars: &HashMap<String, Value>) -> Result<Option<Value>> {
+    match expression {
+        Expression::Literal(value) => Ok(Some(value.clone())),
+        Expression::Variable(name) => Ok(lookup(vars, name).cloned()),
+        Expression::Call(name, args) => {
+            let mut values = Vec::new();
+            for arg in args {
+                match evaluate(arg, vars)? {
+                    Some(value) => values.push(value),
+                    None => return Ok(None),
+                }
+            }
+            render::call_function(name, &values).map(Some)
+        }
+    }
+}
+
+/// Parses `name`, `'text'`, `"text"`, numbers, `true`, `false` and calls like `join(items, ', ')`.
+struct Parser<'a> {
+    rest: &'a str,
+}
+
+impl Parser<'_> {
+    /// Skips whitespace and consumes the char if it comes next.
+    fn eat(&mut self, expected: char) -> bool {
+        self.rest = self.rest.trim_start();
+        match self.rest.strip_prefix(expected) {
+            Some(rest) => {
+                self.rest = rest;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn expression(&mut self) -> Option<Expression> {
+        self.rest = self.rest.trim_start();
+        let first = self.rest.chars().next()?;
+        if first == '\'' || first == '"' {
+            return self.string(first);
+        }
+        if first.is_ascii_digit() || first == '-' {
+            return self.number();
+        }
+        let end = self
+            .rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.'))
+            .unwrap_or(self.rest.len());
+        let name = &self.rest[..end];
+        if !is_variable_name(name) {
+            return None;
+        }
+        self.rest = &self.rest[end..];
+        if self.eat('(') {
+            let mut args = Vec::new();
+            if !self.eat(')') {
+                loop {
+                    args.push(self.expression()?);
+                    if self.eat(')') {
+                        break;
+                    }
+                    if !self.eat(',') {
+                        return None;
+                    }
+                }
+            }
+            return Some(Expression::Call(name.to_string(), args));
+        }
+        Some(match name {
+            "true" => Expression::Literal(Value::Bool(true)),
+            "false" => Expression::Literal(Value::Bool(false)),
+            _ => Expression::Variable(name.to_string()),
+        })
+    }
+
+    /// A quoted string, a backslash escapes the next char.
+    fn string(&mut self, quote: char) -> Option<Expression> {
+        let mut text = String::new();
+        let mut chars = self.rest.char_indices().skip(1);
+        while let Some((index, c)) = chars.next() {
+            match c {
+                '\\' => text.push(chars.next()?.1),
+                c if c == quote => {
+                    self.rest = &self.rest[index + 1..];
+                    return Some(Expression::Literal(Value::String(text)));
+                }
+                c => text.push(c),
+            }
+        }
+        None
+    }
+
+    fn number(&mut self) -> Option<Expression> {
+        let end = self.rest[1..]
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .map_or(self.rest.len(), |end| end + 1);
+        let text = &self.rest[..end];
+        let number = match text.parse::<i64>() {
+            Ok(number) => Number::from(number),
+            Err(_) => Number::from(text.parse::<f64>().ok()?),
+        };
+        self.rest = &self.rest[end..];
+        Some(Expression::Literal(Value::Number(number)))
+    }
+}
+
+/// Parses the text between `{{` and `}}`. `None` if it is not an expression.
+fn parse_expression(text: &str) -> Option<Expression> {
+    let mut parser = Parser { rest: text };
+    let expression = parser.expression()?;
+    match parser.rest.trim().is_empty() {
+        true => Some(expression),
+        false => None,
+    }
+}
+
+/// Gets the value of a variable. Values of maps can be accessed with `variable.key`.
+fn lookup<'a>(vars: &'a HashMap<String, Value>, name: &str) -> Option<&'a Value> {
+    let mut parts = name.split('.');
+    let mut value = vars.get(parts.next()?)?;
+    for key in parts {
+        value = value.as_mapping()?.get(key)?;
+    }
+    Some(value)
+}
+
+/// Converts a scalar value into the string that is inserted into a template.
+pub fn value_to_string(value: &Value) -> Result<String> {
+    match value {
+        Value::String(string) => Ok(string.to_owned()),
+        Value::Number(number) => Ok(number.to_string()),
+        Value::Bool(boolean) => Ok(boolean.to_string()),
+        Value::Null => Ok(String::new()),
+        other => bail!(format!("{:?} can not be converted into a string", other)),
+    }
+}
+
+/// Returns the start, end and expression of every `{{ expression }}` in the text.
+fn find_placeholders(text: &str) -> Vec<(usize, usize, Expression)> {
+    let mut placeholders = Vec::new();
+    let mut offset = 0;
+    while let Some(start) = text[offset..].find("{{") {
+        let start = offset + start;
+        let end = match text[start..].find("}}") {
+            Some(end) => start + end + 2,
+            None => break,
+        };
+        if let Some(expression) = parse_expression(&text[start + 2..end - 2]) {
+            placeholders.push((start, end, expression));
+        }
+        offset = end;
+    }
+    placeholders
+}
+
+fn is_variable_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) if first.is_ascii_alphabetic() || first == '_' => {}
+        _ => return false,
+    }
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{render_str, substitute};
+    use serde_yaml::Value;
+    use std::collections::HashMap;
+
+    fn vars() -> HashMap<String, Value> {
+        let mut vars = HashMap::new();
+        vars.insert("host".to_string(), Value::String("example.org".to_string()));
+        vars.insert("port".to_string(), Value::Number(22.into()));
+        vars
+    }
+
+    #[test]
+    fn render_str_test() {
+        assert_eq!(
+            render_str("ssh {{ host }} -p {{port}}", &vars()).unwrap(),
+            Value::String("ssh example.org -p 22".to_string())
+        );
+    }
+
+    #[test]
+    fn render_str_keeps_type_test() {
+        assert_eq!(
+            render_str("{{ port }}", &vars()).unwrap(),
+            Value::Number(22.into())
+        );
+    }
+
+    #[test]
+    fn render_str_unknown_test() {
+        assert_eq!(
+            render_str("{{ unknown }} {{ host }}", &vars()).unwrap(),
+            Value::String("{{ unknown }} example.org".to_string())
+        );
+    }
+
+    #[test]
+    fn render_str_map_access_test() {
+        let mut vars = vars();
+        vars.insert(
+            "item".to_string(),
+            serde_yaml::from_str("{src: a.txt, dest: /tmp/a.txt}").unwrap(),
+        );
+        assert_eq!(
+            render_str("cp {{ item.src }} {{ item.dest }}", &vars).unwrap(),
+            Value::String("cp a.txt /tmp/a.txt".to_string())
+        );
+    }
+
+    #[test]
+    fn substitute_nested_test() {
+        let mut value: Value = serde_yaml::from_str(
+            "
+        - bash: 'ping {{ host }}'
+        - ssh:
+            commands: ['echo {{ host }}']
+        ",
+        )
+        .unwrap();
+        substitute(&mut value, &vars()).unwrap();
+        let expected: Value = serde_yaml::from_str(
+            "
+        - bash: 'ping example.org'
+        - ssh:
+            commands: ['echo example.org']
+        ",
+        )
+        .unwrap();
+        assert_eq!(value, expected);
+    }
+
+    #[test]
+    fn render_str_functions_test() {
+        let mut vars = vars();
+        vars.insert(
+            "hosts".to_string(),
+            serde_yaml::from_str("[a.org, b.org]").unwrap(),
+        );
+        assert_eq!(
+            render_str("{{ upper(host) }}:{{ str(port) }}", &vars).unwrap(),
+            Value::String("EXAMPLE.ORG:22".to_string())
+        );
+        assert_eq!(
+            render_str("{{ join(hosts, ', ') }}", &vars).unwrap(),
+            Value::String("a.org, b.org".to_string())
+        );
+        assert_eq!(
+            render_str("{{ split(join(hosts, \",\"), ',') }}", &vars).unwrap(),
+            vars["hosts"]
+        );
+        assert_eq!(
+            render_str("{{ match(host, 'org$') }}", &vars).unwrap(),
+            Value::Bool(true)
+        );
+        assert_eq!(
+            render_str(
+                "{{ replace('it\\'s', \"'\", '') }} {{ str(2.5, 2) }}",
+                &vars
+            )
+            .unwrap(),
+            Value::String("its 2.50".to_string())
+        );
+    }
+
+    #[test]
+    fn render_str_functions_untouched_test() {
+        // unknown variables are inserted later, other tools use {{ }} too
+        assert_eq!(
+            render_str("{{ upper(item) }} {{ .Name }} {{ upper(host }}", &vars()).unwrap(),
+            Value::String("{{ upper(item) }} {{ .Name }} {{ upper(host }}".to_string())
+        );
+        assert!(render_str("{{ unknown(host) }}", &vars()).is_err());
+        assert!(render_str("{{ upper(host, port) }}", &vars()).is_err());
+    }
+}